@@ -0,0 +1,71 @@
+use std::io::{self, SeekFrom};
+use std::ops;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::lock_mode::LockMode;
+use crate::sys;
+use crate::unlock;
+
+/// RAII structure used to release the shared read access of an
+/// [`AsyncRwLock`] when dropped.
+///
+/// This structure is created by the [`read`] and [`try_read`] methods on
+/// [`AsyncRwLock`].
+///
+/// [`read`]: crate::AsyncRwLock::read
+/// [`try_read`]: crate::AsyncRwLock::try_read
+/// [`AsyncRwLock`]: crate::AsyncRwLock
+#[must_use = "if unused the AsyncRwLock will immediately unlock"]
+#[derive(Debug)]
+pub struct AsyncRwLockReadGuard<'lock> {
+    lock: &'lock mut sys::RwLock<File>,
+}
+
+impl<'lock> AsyncRwLockReadGuard<'lock> {
+    pub(crate) fn new(lock: &'lock mut sys::RwLock<File>) -> Self {
+        Self { lock }
+    }
+}
+
+impl ops::Deref for AsyncRwLockReadGuard<'_> {
+    type Target = File;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.lock.inner
+    }
+}
+
+impl AsyncRead for AsyncRwLockReadGuard<'_> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncSeek for AsyncRwLockReadGuard<'_> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        Pin::new(&mut self.get_mut().lock.inner).start_seek(position)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_complete(cx)
+    }
+}
+
+impl Drop for AsyncRwLockReadGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        unlock::report(self.lock.release(LockMode::Shared, sys::WHOLE));
+    }
+}
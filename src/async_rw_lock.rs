@@ -0,0 +1,152 @@
+use std::io;
+
+use tokio::fs::File;
+
+use crate::async_read_guard::AsyncRwLockReadGuard;
+use crate::async_write_guard::AsyncRwLockWriteGuard;
+use crate::held::Held;
+use crate::lock_mode::LockMode;
+use crate::sys;
+use crate::Backend;
+
+/// Advisory reader-writer lock for [`tokio::fs::File`].
+///
+/// This works like [`RwLock`], except that waiting for the lock doesn't block
+/// the runtime: the lock is acquired on tokio's blocking thread pool instead.
+/// The guards implement [`AsyncRead`] and [`AsyncWrite`], so the file can be
+/// used for as long as the lock is held.
+///
+/// Dropping a `read` or `write` future before it completes is safe: if the
+/// lock is acquired after the future was dropped, it is released again right
+/// away.
+///
+/// This type is only available with the `tokio` feature enabled.
+///
+/// [`RwLock`]: crate::RwLock
+/// [`AsyncRead`]: tokio::io::AsyncRead
+/// [`AsyncWrite`]: tokio::io::AsyncWrite
+#[derive(Debug)]
+pub struct AsyncRwLock {
+    lock: sys::RwLock<File>,
+}
+
+impl AsyncRwLock {
+    /// Create a new instance.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::AsyncRwLock;
+    /// use tokio::fs::File;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut f = AsyncRwLock::new(File::open("foo.txt").await?);
+    /// # Ok(()) }
+    /// ```
+    #[inline]
+    pub fn new(inner: File) -> Self {
+        Self {
+            lock: sys::RwLock::new(inner),
+        }
+    }
+
+    /// Create a new instance which locks the file using the given [`Backend`].
+    #[inline]
+    pub fn with_backend(inner: File, backend: Backend) -> Self {
+        Self {
+            lock: sys::RwLock::with_backend(inner, backend),
+        }
+    }
+
+    /// Returns the backend this lock uses.
+    #[inline]
+    pub fn backend(&self) -> Backend {
+        self.lock.backend()
+    }
+
+    /// Locks this lock with shared read access, waiting without blocking the
+    /// runtime until it can be acquired.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::AsyncRwLock;
+    /// use tokio::fs::File;
+    /// use tokio::io::AsyncReadExt;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut f = AsyncRwLock::new(File::open("foo.txt").await?);
+    /// let mut s = String::new();
+    /// f.read().await?.read_to_string(&mut s).await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn read(&mut self) -> io::Result<AsyncRwLockReadGuard<'_>> {
+        self.acquire(LockMode::Shared).await?;
+        Ok(AsyncRwLockReadGuard::new(&mut self.lock))
+    }
+
+    /// Attempts to acquire this lock with shared read access.
+    ///
+    /// If the lock could not be acquired at this time, then [`Err`] is returned
+    /// with [`ErrorKind::WouldBlock`].
+    ///
+    /// [`ErrorKind::WouldBlock`]: std::io::ErrorKind::WouldBlock
+    #[inline]
+    pub fn try_read(&mut self) -> io::Result<AsyncRwLockReadGuard<'_>> {
+        self.lock.lock(LockMode::Shared, sys::WHOLE, false)?;
+        Ok(AsyncRwLockReadGuard::new(&mut self.lock))
+    }
+
+    /// Locks this lock with exclusive write access, waiting without blocking
+    /// the runtime until it can be acquired.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::AsyncRwLock;
+    /// use tokio::fs::File;
+    /// use tokio::io::AsyncWriteExt;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut f = AsyncRwLock::new(File::create("foo.txt").await?);
+    /// let mut guard = f.write().await?;
+    /// guard.write_all(b"chashu cat").await?;
+    /// guard.flush().await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn write(&mut self) -> io::Result<AsyncRwLockWriteGuard<'_>> {
+        self.acquire(LockMode::Exclusive).await?;
+        Ok(AsyncRwLockWriteGuard::new(&mut self.lock))
+    }
+
+    /// Attempts to lock this lock with exclusive write access.
+    ///
+    /// If the lock could not be acquired at this time, then [`Err`] is returned
+    /// with [`ErrorKind::WouldBlock`].
+    ///
+    /// [`ErrorKind::WouldBlock`]: std::io::ErrorKind::WouldBlock
+    #[inline]
+    pub fn try_write(&mut self) -> io::Result<AsyncRwLockWriteGuard<'_>> {
+        self.lock.lock(LockMode::Exclusive, sys::WHOLE, false)?;
+        Ok(AsyncRwLockWriteGuard::new(&mut self.lock))
+    }
+
+    /// Consumes this `AsyncRwLock`, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> File {
+        self.lock.into_inner()
+    }
+
+    /// Acquires the whole file on the blocking thread pool.
+    ///
+    /// The worker locks through a duplicate of the file descriptor, so it
+    /// doesn't need to borrow `self` and can outlive this future.
+    async fn acquire(&self, mode: LockMode) -> io::Result<()> {
+        let lock = self.lock.detach()?;
+        let held = tokio::task::spawn_blocking(move || Held::acquire(lock, mode, sys::WHOLE))
+            .await
+            .map_err(io::Error::other)??;
+        held.adopt();
+        Ok(())
+    }
+}
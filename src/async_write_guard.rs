@@ -0,0 +1,105 @@
+use std::io::{self, SeekFrom};
+use std::ops;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+use crate::lock_mode::LockMode;
+use crate::sys;
+use crate::unlock;
+
+/// RAII structure used to release the exclusive write access of an
+/// [`AsyncRwLock`] when dropped.
+///
+/// This structure is created by the [`write`] and [`try_write`] methods on
+/// [`AsyncRwLock`].
+///
+/// [`tokio::fs::File`] finishes writes in the background, so a write may
+/// still be in flight when its future completes. Call [`flush`] before
+/// dropping the guard to make sure every write happened while the lock was
+/// held.
+///
+/// [`write`]: crate::AsyncRwLock::write
+/// [`try_write`]: crate::AsyncRwLock::try_write
+/// [`AsyncRwLock`]: crate::AsyncRwLock
+/// [`flush`]: tokio::io::AsyncWrite::poll_flush
+#[must_use = "if unused the AsyncRwLock will immediately unlock"]
+#[derive(Debug)]
+pub struct AsyncRwLockWriteGuard<'lock> {
+    lock: &'lock mut sys::RwLock<File>,
+}
+
+impl<'lock> AsyncRwLockWriteGuard<'lock> {
+    pub(crate) fn new(lock: &'lock mut sys::RwLock<File>) -> Self {
+        Self { lock }
+    }
+}
+
+impl ops::Deref for AsyncRwLockWriteGuard<'_> {
+    type Target = File;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.lock.inner
+    }
+}
+
+impl ops::DerefMut for AsyncRwLockWriteGuard<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.lock.inner
+    }
+}
+
+impl AsyncRead for AsyncRwLockWriteGuard<'_> {
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for AsyncRwLockWriteGuard<'_> {
+    #[inline]
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for AsyncRwLockWriteGuard<'_> {
+    #[inline]
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        Pin::new(&mut self.get_mut().lock.inner).start_seek(position)
+    }
+
+    #[inline]
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.get_mut().lock.inner).poll_complete(cx)
+    }
+}
+
+impl Drop for AsyncRwLockWriteGuard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        unlock::report(self.lock.release(LockMode::Exclusive, sys::WHOLE));
+    }
+}
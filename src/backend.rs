@@ -0,0 +1,22 @@
+/// The locking mechanism used by a [`RwLock`].
+///
+/// [`RwLock`]: crate::RwLock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Backend {
+    /// The platform's default file locks: `flock(2)` on Unix (`fcntl(2)` on
+    /// Solaris) and `LockFileEx` on Windows.
+    #[default]
+    Native,
+    /// Linux open file description locks (`F_OFD_SETLK`).
+    ///
+    /// Like `flock(2)` these belong to the open file description rather than
+    /// to the process, but they are real `fcntl(2)` locks: they also work on
+    /// NFS and they can cover byte ranges. Being `fcntl(2)` locks, shared
+    /// locks require the file to be open for reading and exclusive locks
+    /// require it to be open for writing. Kernels older than 3.15 reject
+    /// them with `EINVAL`, in which case the lock falls back to
+    /// [`Backend::Native`].
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    Ofd,
+}
@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+#[cfg(unix)]
+use std::thread::{self, ThreadId};
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::sys::Thread;
+
+/// A handle to cancel blocking lock acquisitions from another thread.
+///
+/// Pass the token to [`RwLock::read_cancellable`] or
+/// [`RwLock::write_cancellable`], and call [`CancelToken::cancel`] on a clone
+/// of it to make them give up. Once cancelled, a token stays cancelled: every
+/// later acquisition using it fails right away.
+///
+/// On Unix the waiting threads are woken up by sending them `SIGURG`, which
+/// is ignored by default. If the signal has no handler yet, a handler which
+/// does nothing is installed, so the signal interrupts blocking system calls.
+/// A handler installed by the application, or an explicit `SIG_IGN`, is left
+/// alone. If the signal then can't interrupt system calls, because it is
+/// ignored or its handler uses `SA_RESTART`, the acquisition polls the lock
+/// instead. The installed handler stays in place, which affects the whole
+/// program; see the [crate documentation](crate#signals). On Windows the
+/// acquisition always polls the lock, as `LockFileEx` can't be interrupted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::{CancelToken, RwLock};
+/// use std::fs::File;
+/// use std::thread;
+///
+/// fn main() -> std::io::Result<()> {
+///     let token = CancelToken::new();
+///     let canceller = token.clone();
+///     let waiter = thread::spawn(move || {
+///         let mut f = RwLock::new(File::open("foo.txt")?);
+///         let guard = f.write_cancellable(&token)?;
+///         Ok::<_, std::io::Error>(())
+///     });
+///     canceller.cancel();
+///     let _ = waiter.join();
+///     Ok(())
+/// }
+/// ```
+///
+/// [`RwLock::read_cancellable`]: crate::RwLock::read_cancellable
+/// [`RwLock::write_cancellable`]: crate::RwLock::write_cancellable
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// The threads currently acquiring a lock with this token.
+    #[cfg(unix)]
+    waiters: Mutex<Vec<(ThreadId, Thread)>>,
+}
+
+impl CancelToken {
+    /// Creates a token which isn't cancelled.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every acquisition using this token, now and later.
+    ///
+    /// Returns once the threads acquiring a lock with this token have given
+    /// up, or acquired the lock in the meantime. The interrupted
+    /// acquisitions fail with an error which [`Error::from`] classifies as
+    /// [`Error::Cancelled`], and leave nothing locked.
+    ///
+    /// [`Error::from`]: crate::Error
+    /// [`Error::Cancelled`]: crate::Error::Cancelled
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        // A thread may be signalled right before it blocks, so keep
+        // signalling until it is gone.
+        #[cfg(unix)]
+        loop {
+            let waiters = self.waiters();
+            if waiters.is_empty() {
+                break;
+            }
+            for (_, thread) in waiters.iter() {
+                thread.interrupt();
+            }
+            drop(waiters);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Returns whether this token was cancelled.
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Registers the current thread as acquiring a lock with this token,
+    /// until the returned value is dropped.
+    ///
+    /// Returns `None` if the thread can't be interrupted, in which case the
+    /// caller has to poll.
+    #[cfg(unix)]
+    pub(crate) fn register(&self) -> Option<Waiter<'_>> {
+        let id = thread::current().id();
+        self.waiters().push((id, Thread::current()?));
+        Some(Waiter { token: self, id })
+    }
+
+    #[cfg(unix)]
+    fn waiters(&self) -> MutexGuard<'_, Vec<(ThreadId, Thread)>> {
+        self.inner
+            .waiters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A thread acquiring a lock with a [`CancelToken`].
+#[cfg(unix)]
+pub(crate) struct Waiter<'a> {
+    token: &'a CancelToken,
+    id: ThreadId,
+}
+
+#[cfg(unix)]
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let mut waiters = self.token.waiters();
+        if let Some(index) = waiters.iter().position(|(id, _)| *id == self.id) {
+            waiters.swap_remove(index);
+        }
+    }
+}
+
+/// Returns the error of a cancelled acquisition.
+pub(crate) fn cancelled() -> io::Error {
+    io::Error::other(Cancelled)
+}
+
+/// Whether an error is the one returned by [`cancelled`].
+pub(crate) fn is_cancelled(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|err| err.is::<Cancelled>())
+}
+
+#[derive(Debug)]
+struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("lock acquisition was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
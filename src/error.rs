@@ -0,0 +1,171 @@
+use std::fmt;
+use std::io::{self, ErrorKind};
+
+use crate::cancel_token;
+
+/// The ways locking a file can fail.
+///
+/// Every method of this crate returns an [`io::Error`], so existing code
+/// keeps working with `?` and [`io::Error::kind`]. Convert the error with
+/// [`Error::from`] to tell the failures of the locking syscalls apart. Each
+/// variant keeps the original error, and converting back into an
+/// [`io::Error`] returns it unchanged.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::RwLock;
+/// use std::fs::File;
+///
+/// fn main() -> std::io::Result<()> {
+///     let mut f = RwLock::new(File::open("foo.txt")?);
+///     match f.try_write().map_err(fd_lock::Error::from) {
+///         Ok(guard) => println!("locked"),
+///         Err(fd_lock::Error::Contended(_)) => println!("busy, try again later"),
+///         Err(err) => return Err(err.into()),
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The lock is held elsewhere, and the call was not allowed to block.
+    Contended(io::Error),
+    /// A signal interrupted the call before the lock was acquired (`EINTR`).
+    Interrupted(io::Error),
+    /// Waiting for the lock would deadlock with a process waiting for a lock
+    /// this process holds (`EDEADLK`), or with a lock this process holds
+    /// through another handle, as detected with
+    /// [`SameProcessPolicy::Error`].
+    ///
+    /// [`SameProcessPolicy::Error`]: crate::SameProcessPolicy::Error
+    Deadlock(io::Error),
+    /// The system ran out of lock records (`ENOLCK`). NFS servers without a
+    /// lock manager report this as well.
+    NoLocksAvailable(io::Error),
+    /// The file system or the platform can't lock the file this way
+    /// (`EOPNOTSUPP`).
+    Unsupported(io::Error),
+    /// The file isn't open for the access the lock requires (`EBADF`).
+    /// `fcntl(2)` locks, which includes [`Backend::Ofd`], need the file open
+    /// for writing to lock it exclusively and for reading to share it.
+    ///
+    /// [`Backend::Ofd`]: crate::Backend
+    BadAccessMode(io::Error),
+    /// The acquisition was cancelled with [`CancelToken::cancel`].
+    ///
+    /// [`CancelToken::cancel`]: crate::CancelToken::cancel
+    Cancelled(io::Error),
+    /// Any other error.
+    Other(io::Error),
+}
+
+impl Error {
+    /// Returns the error kind of the underlying [`io::Error`].
+    #[inline]
+    pub fn kind(&self) -> ErrorKind {
+        self.io_error().kind()
+    }
+
+    /// Returns the underlying [`io::Error`].
+    #[inline]
+    pub fn io_error(&self) -> &io::Error {
+        match self {
+            Error::Contended(err)
+            | Error::Interrupted(err)
+            | Error::Deadlock(err)
+            | Error::NoLocksAvailable(err)
+            | Error::Unsupported(err)
+            | Error::BadAccessMode(err)
+            | Error::Cancelled(err)
+            | Error::Other(err) => err,
+        }
+    }
+}
+
+/// Classifies an error returned by this crate.
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        if cancel_token::is_cancelled(&err) {
+            return Error::Cancelled(err);
+        }
+
+        #[cfg(unix)]
+        {
+            use rustix::io::Errno;
+
+            match os_error(&err).map(Errno::from_raw_os_error) {
+                Some(Errno::INTR) => return Error::Interrupted(err),
+                Some(Errno::DEADLK) => return Error::Deadlock(err),
+                Some(Errno::NOLCK) => return Error::NoLocksAvailable(err),
+                // These are the same value on some platforms but not others.
+                Some(errno) if errno == Errno::OPNOTSUPP || errno == Errno::NOTSUP => {
+                    return Error::Unsupported(err)
+                }
+                Some(Errno::BADF) => return Error::BadAccessMode(err),
+                _ => {}
+            }
+        }
+
+        match err.kind() {
+            ErrorKind::WouldBlock => Error::Contended(err),
+            ErrorKind::Interrupted => Error::Interrupted(err),
+            ErrorKind::Deadlock => Error::Deadlock(err),
+            ErrorKind::Unsupported => Error::Unsupported(err),
+            _ => Error::Other(err),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    #[inline]
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Contended(err)
+            | Error::Interrupted(err)
+            | Error::Deadlock(err)
+            | Error::NoLocksAvailable(err)
+            | Error::Unsupported(err)
+            | Error::BadAccessMode(err)
+            | Error::Cancelled(err)
+            | Error::Other(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.io_error().fmt(f)
+    }
+}
+
+impl std::error::Error for Error {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.io_error().source()
+    }
+}
+
+/// Finds the OS error code behind `err`, looking through the errors it
+/// wraps, such as the ones naming the file of a `LockFile`.
+#[cfg(unix)]
+fn os_error(err: &io::Error) -> Option<i32> {
+    let mut next: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(err) = next {
+        next = match err.downcast_ref::<io::Error>() {
+            Some(err) => match err.raw_os_error() {
+                Some(code) => return Some(code),
+                None => err.get_ref().map(|err| err as _),
+            },
+            None => err.source(),
+        };
+    }
+    None
+}
+
+/// Returns the error of an acquisition which timed out.
+pub(crate) fn timed_out() -> io::Error {
+    io::Error::new(ErrorKind::TimedOut, "timed out waiting for the file lock")
+}
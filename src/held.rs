@@ -0,0 +1,49 @@
+use std::io;
+
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::sys;
+use crate::unlock;
+
+/// A lock acquired on a worker thread on behalf of an async task.
+///
+/// The task may be cancelled while the worker is still blocked on the lock.
+/// To avoid leaking it, the lock is released again when this is dropped, until
+/// the task takes ownership of it through [`Held::adopt`].
+#[derive(Debug)]
+pub(crate) struct Held {
+    lock: Option<sys::RwLock<sys::OwnedOpenFile>>,
+    mode: LockMode,
+    range: ByteRange,
+}
+
+impl Held {
+    /// Blocks the current thread until the range is locked.
+    pub(crate) fn acquire(
+        lock: sys::RwLock<sys::OwnedOpenFile>,
+        mode: LockMode,
+        range: ByteRange,
+    ) -> io::Result<Self> {
+        lock.lock(mode, range, true)?;
+        Ok(Held {
+            lock: Some(lock),
+            mode,
+            range,
+        })
+    }
+
+    /// Hands the lock over to the caller, who becomes responsible for
+    /// unlocking it.
+    pub(crate) fn adopt(mut self) {
+        self.lock = None;
+    }
+}
+
+impl Drop for Held {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(lock) = &self.lock {
+            unlock::report(lock.release(self.mode, self.range));
+        }
+    }
+}
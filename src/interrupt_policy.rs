@@ -0,0 +1,21 @@
+/// What a blocking acquisition does when a signal interrupts it.
+///
+/// On Unix a signal arriving while a thread waits for a lock makes the
+/// locking syscall fail with `EINTR`. By default this is returned as an
+/// `ErrorKind::Interrupted` error, which lets signals cancel a wait. Set
+/// [`InterruptPolicy::Retry`] with [`RwLock::set_interrupt_policy`] or pass
+/// it to [`RwLock::read_with`] and [`RwLock::write_with`] to keep waiting
+/// instead. Windows never interrupts an acquisition.
+///
+/// [`RwLock::set_interrupt_policy`]: crate::RwLock::set_interrupt_policy
+/// [`RwLock::read_with`]: crate::RwLock::read_with
+/// [`RwLock::write_with`]: crate::RwLock::write_with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum InterruptPolicy {
+    /// Give up and return an `ErrorKind::Interrupted` error.
+    #[default]
+    Return,
+    /// Retry the acquisition until it succeeds or fails for another reason.
+    Retry,
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+use crate::lock_file::LockFileOptions;
+use crate::named;
+use crate::owned_read_guard::OwnedRwLockReadGuard;
+use crate::owned_write_guard::OwnedRwLockWriteGuard;
+use crate::rw_lock::RwLock;
+
+/// A lock per key, each backed by a file in a directory.
+///
+/// Keys are mapped to file names like [`named`] maps lock names: characters
+/// which aren't safe in a file name are replaced, and a hash of the key is
+/// appended when any are. Processes which use the same directory get the
+/// same lock for the same key.
+///
+/// The file of a key is opened once and shared by every guard this
+/// `KeyedLocks` hands out for it, until the last of them is dropped. Lock
+/// files are left in place when released; [`KeyedLocks::gc`] removes the
+/// ones nobody holds.
+///
+/// [`named`]: crate::named
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::KeyedLocks;
+///
+/// fn main() -> std::io::Result<()> {
+///     let locks = KeyedLocks::new("target/cache/locks")?;
+///     let guard = locks.write("serde-1.0.219")?;
+///     // Fill the cache entry.
+///     drop(guard);
+///     locks.gc()?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct KeyedLocks {
+    dir: PathBuf,
+    handles: Mutex<Handles>,
+}
+
+/// The files of the keys which are in use, by file name.
+#[derive(Debug, Default)]
+struct Handles {
+    files: HashMap<String, Weak<RwLock<File>>>,
+    /// The number of files after which the map is cleared of the ones which
+    /// are no longer in use.
+    prune_at: usize,
+}
+
+impl KeyedLocks {
+    /// Creates the locks in the directory `dir`, creating it and its parents
+    /// if they don't exist.
+    ///
+    /// # Errors
+    ///
+    /// If the directory could not be created the error is returned.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            handles: Mutex::default(),
+        })
+    }
+
+    /// Returns the directory the lock files are in.
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the lock file of `key`.
+    ///
+    /// # Errors
+    ///
+    /// If `key` is empty an `ErrorKind::InvalidInput` error is returned.
+    #[inline]
+    pub fn path(&self, key: &str) -> io::Result<PathBuf> {
+        Ok(self.dir.join(named::file_name(key)?))
+    }
+
+    /// Locks `key` with shared read access, blocking the current thread
+    /// until it can be acquired.
+    ///
+    /// See [`RwLock::read`] for details.
+    pub fn read(&self, key: &str) -> io::Result<OwnedRwLockReadGuard<File>> {
+        self.acquire(key, RwLock::read_owned)
+    }
+
+    /// Attempts to lock `key` with shared read access.
+    ///
+    /// See [`RwLock::try_read`] for details.
+    pub fn try_read(&self, key: &str) -> io::Result<OwnedRwLockReadGuard<File>> {
+        self.acquire(key, RwLock::try_read_owned)
+    }
+
+    /// Locks `key` with exclusive write access, blocking the current thread
+    /// until it can be acquired.
+    ///
+    /// See [`RwLock::write`] for details.
+    pub fn write(&self, key: &str) -> io::Result<OwnedRwLockWriteGuard<File>> {
+        self.acquire(key, RwLock::write_owned)
+    }
+
+    /// Attempts to lock `key` with exclusive write access.
+    ///
+    /// See [`RwLock::try_write`] for details.
+    pub fn try_write(&self, key: &str) -> io::Result<OwnedRwLockWriteGuard<File>> {
+        self.acquire(key, RwLock::try_write_owned)
+    }
+
+    /// Removes the lock files no process holds, returning how many were
+    /// removed.
+    ///
+    /// A file is only removed while this holds its lock exclusively, and
+    /// every acquisition checks that the file it locked is still in place,
+    /// opening it anew if not. So this never races with a process which
+    /// acquires the same key, as long as every process uses `KeyedLocks`.
+    /// Files of keys this `KeyedLocks` has open are skipped.
+    ///
+    /// On Windows a file can't be replaced while another process has it
+    /// open, so nothing is removed there.
+    ///
+    /// # Errors
+    ///
+    /// If the directory could not be read the error is returned. Files
+    /// which could not be removed are skipped.
+    pub fn gc(&self) -> io::Result<usize> {
+        // Keep other threads from opening files while they are removed.
+        let handles = self.handles();
+        let mut removed = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            let in_use = handles
+                .files
+                .get(name)
+                .is_some_and(|file| file.strong_count() > 0);
+            if !name.ends_with(".lock") || in_use {
+                continue;
+            }
+            if remove_unheld(&entry.path()).unwrap_or(false) {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Locks the file of `key` with `lock`, opening it anew if it was
+    /// removed before it was locked.
+    fn acquire<G>(
+        &self,
+        key: &str,
+        lock: impl Fn(Arc<RwLock<File>>) -> io::Result<G>,
+    ) -> io::Result<G>
+    where
+        G: std::ops::Deref<Target = File>,
+    {
+        let name = named::file_name(key)?;
+        let path = self.dir.join(&name);
+        loop {
+            let file = self.open(&name, &path)?;
+            let guard = lock(file.clone())?;
+            if is_current(&guard, &path)? {
+                return Ok(guard);
+            }
+            drop(guard);
+            self.forget(&name, &file);
+        }
+    }
+
+    /// Returns the open file of a key, opening it if it isn't yet.
+    fn open(&self, name: &str, path: &Path) -> io::Result<Arc<RwLock<File>>> {
+        let mut handles = self.handles();
+        if let Some(file) = handles.files.get(name).and_then(Weak::upgrade) {
+            return Ok(file);
+        }
+
+        let file = Arc::new(options().open(path)?.into_inner());
+        if handles.files.len() >= handles.prune_at {
+            handles.files.retain(|_, file| file.strong_count() > 0);
+            handles.prune_at = (handles.files.len() * 2).max(64);
+        }
+        handles.files.insert(name.to_owned(), Arc::downgrade(&file));
+        Ok(file)
+    }
+
+    /// Forgets the open file of a key which was removed, unless it was
+    /// replaced already.
+    fn forget(&self, name: &str, file: &Arc<RwLock<File>>) {
+        let mut handles = self.handles();
+        if let Some(current) = handles.files.get(name) {
+            if Weak::as_ptr(current) == Arc::as_ptr(file) {
+                handles.files.remove(name);
+            }
+        }
+    }
+
+    fn handles(&self) -> MutexGuard<'_, Handles> {
+        // The map is never left inconsistent, so a panic elsewhere doesn't
+        // matter.
+        self.handles.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+fn options() -> LockFileOptions {
+    let mut options = LockFileOptions::new();
+    #[cfg(unix)]
+    options.nofollow(true);
+    options
+}
+
+/// Returns whether `file` is still the file at `path`.
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    match std::fs::symlink_metadata(path) {
+        Ok(current) => Ok(opened.dev() == current.dev() && opened.ino() == current.ino()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns whether `file` is still the file at `path`. Files are never
+/// removed on Windows.
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Removes the lock file at `path` if nobody holds it, returning whether it
+/// did.
+#[cfg(unix)]
+fn remove_unheld(path: &Path) -> io::Result<bool> {
+    let mut options = options();
+    let mut lock = options.create(false).open(path)?.into_inner();
+    let guard = match lock.try_write() {
+        Ok(guard) => guard,
+        Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    // Another process may have removed and recreated it before it was
+    // locked.
+    if !is_current(&guard, path)? {
+        return Ok(false);
+    }
+    std::fs::remove_file(path)?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+fn remove_unheld(_path: &Path) -> io::Result<bool> {
+    Ok(false)
+}
@@ -8,6 +8,28 @@
 //! same program. But do not use this to prevent actors from accessing or
 //! modifying files.
 //!
+//! # Signals
+//!
+//! On Unix, [`CancelToken`] wakes up waiting threads by sending them
+//! `SIGURG`. On Linux and Android, timed waits such as
+//! [`RwLock::read_timeout`], [`RwLock::read_with_progress`] and
+//! [`LockOptions::max_wait`] also block in the kernel, with a per-thread
+//! timer which sends `SIGURG` once the deadline passes, and every
+//! millisecond after that until the wait is over.
+//!
+//! For the signal to interrupt the wait, the first such call installs a
+//! process-wide `SIGURG` handler which does nothing, without `SA_RESTART`,
+//! unless the application already handles the signal or explicitly ignores
+//! it with `SIG_IGN`. The handler stays
+//! installed. From then on, any `SIGURG` the process receives, such as one
+//! for out-of-band TCP data or one another runtime uses to preempt its
+//! threads, makes a blocking system call of the thread it is delivered to
+//! fail with `EINTR` instead of being ignored.
+//!
+//! To avoid this, install a `SIGURG` handler with `SA_RESTART`, or set it to
+//! `SIG_IGN`, before taking any lock. Timed and cancellable waits then sleep
+//! between attempts instead of blocking in the kernel.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -32,12 +54,69 @@
 #![deny(missing_debug_implementations, nonstandard_style)]
 #![cfg_attr(doc, warn(missing_docs, rustdoc::missing_doc_code_examples))]
 
+#[cfg(feature = "tokio")]
+mod async_read_guard;
+#[cfg(feature = "tokio")]
+mod async_rw_lock;
+#[cfg(feature = "tokio")]
+mod async_write_guard;
+mod backend;
+mod cancel_token;
+mod error;
+#[cfg(any(feature = "async", feature = "tokio"))]
+mod held;
+mod interrupt_policy;
+mod keyed_locks;
+mod liveness;
+mod lock_file;
+mod lock_holder;
+mod lock_mode;
+mod lock_options;
+mod lock_set;
+mod lock_wait;
+mod named;
+mod owned_read_guard;
+mod owned_write_guard;
+mod pidfile;
+mod range;
 mod read_guard;
 mod rw_lock;
+mod same_process_policy;
+#[cfg(unix)]
+mod single_instance;
+mod unlock;
+mod upgradable_read_guard;
 mod write_guard;
 
 pub(crate) mod sys;
 
+#[cfg(feature = "tokio")]
+pub use async_read_guard::AsyncRwLockReadGuard;
+#[cfg(feature = "tokio")]
+pub use async_rw_lock::AsyncRwLock;
+#[cfg(feature = "tokio")]
+pub use async_write_guard::AsyncRwLockWriteGuard;
+pub use backend::Backend;
+pub use cancel_token::CancelToken;
+pub use error::Error;
+pub use interrupt_policy::InterruptPolicy;
+pub use keyed_locks::KeyedLocks;
+pub use liveness::Liveness;
+pub use lock_file::{LockFile, LockFileOptions};
+pub use lock_holder::LockHolder;
+pub use lock_mode::LockMode;
+pub use lock_options::LockOptions;
+pub use lock_set::{LockSet, LockSetGuard};
+pub use lock_wait::LockWait;
+pub use named::{named, named_path, NamedScope};
+pub use owned_read_guard::OwnedRwLockReadGuard;
+pub use owned_write_guard::OwnedRwLockWriteGuard;
+pub use pidfile::{Pidfile, PidfileHolder, PidfileOptions};
 pub use read_guard::RwLockReadGuard;
 pub use rw_lock::RwLock;
+pub use same_process_policy::{set_same_process_policy, SameProcessPolicy};
+#[cfg(unix)]
+pub use single_instance::{Instance, Requests, SingleInstance};
+pub use unlock::{set_unlock_hook, take_unlock_hook, UnlockError};
+pub use upgradable_read_guard::RwLockUpgradableReadGuard;
 pub use write_guard::RwLockWriteGuard;
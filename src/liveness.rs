@@ -0,0 +1,50 @@
+use std::time::SystemTime;
+
+use crate::sys;
+
+/// Whether a process which held a lock is still running.
+///
+/// This is returned by [`Liveness::of_process`] and
+/// [`PidfileHolder::liveness`].
+///
+/// [`PidfileHolder::liveness`]: crate::PidfileHolder::liveness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Liveness {
+    /// The process is running.
+    Alive,
+    /// The process has exited, or its PID now belongs to a process which
+    /// started later.
+    Dead,
+    /// The process can't be checked from here, for example because it runs
+    /// on another machine or in another PID namespace.
+    Unknown,
+}
+
+impl Liveness {
+    /// Checks whether the process with the PID `pid` is still running.
+    ///
+    /// PIDs are reused once a process exits. If `started` is given, a
+    /// process which started after it is taken to be a different one, so
+    /// the original holder is reported as [`Liveness::Dead`]. On Linux the
+    /// start time is read from `/proc/<pid>/stat`; other platforms only
+    /// check whether some process has the PID.
+    ///
+    /// The PID is looked up in the PID namespace of the current process.
+    /// Windows always returns [`Liveness::Unknown`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::Liveness;
+    ///
+    /// let liveness = Liveness::of_process(1234, None);
+    /// if liveness == Liveness::Dead {
+    ///     println!("process 1234 is gone");
+    /// }
+    /// ```
+    #[inline]
+    pub fn of_process(pid: u32, started: Option<SystemTime>) -> Self {
+        sys::process_liveness(pid, started)
+    }
+}
@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::read_guard::RwLockReadGuard;
+use crate::rw_lock::RwLock;
+use crate::write_guard::RwLockWriteGuard;
+use crate::Backend;
+
+/// An advisory reader-writer lock on a file, opened by path.
+///
+/// This wraps a [`RwLock<File>`], taking care of opening the file: it is
+/// created if it doesn't exist yet, opened for both reading and writing so
+/// every backend can lock it, and never truncated. The path is kept, so
+/// errors can name the file they are about.
+///
+/// Use [`LockFile::options`] to change how the file is opened.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::LockFile;
+/// use std::io::prelude::*;
+///
+/// fn main() -> std::io::Result<()> {
+///     let mut f = LockFile::open("foo.lock")?;
+///     write!(f.write()?, "chashu cat")?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LockFile {
+    lock: RwLock<File>,
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Opens the file at `path` with the default options, creating it if it
+    /// doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// If the file could not be opened the error is returned, naming the
+    /// path.
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        LockFileOptions::new().open(path)
+    }
+
+    /// Returns a builder to configure how the file is opened.
+    #[inline]
+    pub fn options() -> LockFileOptions {
+        LockFileOptions::new()
+    }
+
+    /// Returns the path this lock file was opened with.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Locks this file with shared read access, blocking the current thread
+    /// until it can be acquired.
+    ///
+    /// See [`RwLock::read`] for details.
+    #[inline]
+    pub fn read(&self) -> io::Result<RwLockReadGuard<'_, File>> {
+        self.lock
+            .read()
+            .map_err(|err| path_error(&self.path, err, "lock"))
+    }
+
+    /// Attempts to acquire this file with shared read access.
+    ///
+    /// See [`RwLock::try_read`] for details.
+    #[inline]
+    pub fn try_read(&self) -> io::Result<RwLockReadGuard<'_, File>> {
+        self.lock
+            .try_read()
+            .map_err(|err| path_error(&self.path, err, "lock"))
+    }
+
+    /// Locks this file with exclusive write access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// See [`RwLock::write`] for details.
+    #[inline]
+    pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, File>> {
+        self.lock
+            .write()
+            .map_err(|err| path_error(&self.path, err, "lock"))
+    }
+
+    /// Attempts to lock this file with exclusive write access.
+    ///
+    /// See [`RwLock::try_write`] for details.
+    #[inline]
+    pub fn try_write(&mut self) -> io::Result<RwLockWriteGuard<'_, File>> {
+        self.lock
+            .try_write()
+            .map_err(|err| path_error(&self.path, err, "lock"))
+    }
+
+    /// Returns the underlying lock.
+    #[inline]
+    pub fn get_ref(&self) -> &RwLock<File> {
+        &self.lock
+    }
+
+    /// Returns the underlying lock mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut RwLock<File> {
+        &mut self.lock
+    }
+
+    /// Consumes this `LockFile`, returning the underlying lock.
+    #[inline]
+    pub fn into_inner(self) -> RwLock<File> {
+        self.lock
+    }
+}
+
+/// Options to configure how a [`LockFile`] is opened.
+///
+/// This is created by [`LockFile::options`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::LockFile;
+///
+/// fn main() -> std::io::Result<()> {
+///     let f = LockFile::options().create(false).open("foo.lock")?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LockFileOptions {
+    create: bool,
+    create_new: bool,
+    backend: Backend,
+    #[cfg(unix)]
+    mode: u32,
+    #[cfg(unix)]
+    cloexec: bool,
+    #[cfg(unix)]
+    nofollow: bool,
+}
+
+impl LockFileOptions {
+    /// Creates the default options: create the file if it's missing, with
+    /// the native backend.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            create: true,
+            create_new: false,
+            backend: Backend::Native,
+            #[cfg(unix)]
+            mode: 0o666,
+            #[cfg(unix)]
+            cloexec: true,
+            #[cfg(unix)]
+            nofollow: false,
+        }
+    }
+
+    /// Sets whether the file is created if it doesn't exist. Defaults to
+    /// `true`. The file is never truncated either way.
+    #[inline]
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets whether opening fails with `ErrorKind::AlreadyExists` if the file
+    /// exists, so a file is only opened if it was just created. Defaults to
+    /// `false`. This overrides [`LockFileOptions::create`].
+    #[inline]
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Sets the backend used to lock the file. Defaults to
+    /// [`Backend::Native`].
+    #[inline]
+    pub fn backend(&mut self, backend: Backend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets the permissions a newly created file gets, before the process
+    /// umask is applied. Defaults to `0o666`.
+    #[cfg(unix)]
+    #[inline]
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets whether the file descriptor is closed when the process calls
+    /// `exec`. Defaults to `true`.
+    ///
+    /// `flock(2)` and OFD locks are shared with a child process which
+    /// inherits the descriptor, so the lock stays held until the child
+    /// exits as well.
+    #[cfg(unix)]
+    #[inline]
+    pub fn cloexec(&mut self, cloexec: bool) -> &mut Self {
+        self.cloexec = cloexec;
+        self
+    }
+
+    /// Sets whether opening fails if the last component of the path is a
+    /// symbolic link. Defaults to `false`.
+    #[cfg(unix)]
+    #[inline]
+    pub fn nofollow(&mut self, nofollow: bool) -> &mut Self {
+        self.nofollow = nofollow;
+        self
+    }
+
+    /// Opens the file at `path` with these options.
+    ///
+    /// # Errors
+    ///
+    /// If the file could not be opened the error is returned, naming the
+    /// path.
+    pub fn open(&self, path: impl AsRef<Path>) -> io::Result<LockFile> {
+        let path = path.as_ref();
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(self.create);
+        options.create_new(self.create_new);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            options.mode(self.mode);
+            if self.nofollow {
+                options.custom_flags(rustix::fs::OFlags::NOFOLLOW.bits() as i32);
+            }
+        }
+
+        let file = options
+            .open(path)
+            .map_err(|err| path_error(path, err, "open"))?;
+
+        #[cfg(unix)]
+        if !self.cloexec {
+            use rustix::io::{fcntl_setfd, FdFlags};
+
+            fcntl_setfd(&file, FdFlags::empty())
+                .map_err(|err| path_error(path, err.into(), "open"))?;
+        }
+
+        Ok(LockFile {
+            lock: RwLock::with_backend(file, self.backend),
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Default for LockFileOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps an error so its message names the file it is about. The error
+/// kind is kept, so callers can still match on it.
+fn path_error(path: &Path, err: io::Error, action: &'static str) -> io::Error {
+    let kind = err.kind();
+    io::Error::new(
+        kind,
+        PathError {
+            path: path.to_owned(),
+            action,
+            source: err,
+        },
+    )
+}
+
+#[derive(Debug)]
+struct PathError {
+    path: PathBuf,
+    action: &'static str,
+    source: io::Error,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (action, path) = (self.action, self.path.display());
+        write!(f, "failed to {action} `{path}`: {}", self.source)
+    }
+}
+
+impl Error for PathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn path_errors_keep_their_classification() {
+        let err = io::Error::from_raw_os_error(libc::ENOLCK);
+        let err = path_error(Path::new("foo.lock"), err, "lock");
+        assert!(err.to_string().contains("foo.lock"));
+        assert!(matches!(
+            crate::Error::from(err),
+            crate::Error::NoLocksAvailable(_)
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use crate::lock_mode::LockMode;
+use crate::range::{ByteRange, MAX_END};
+
+/// A lock which stands in the way of acquiring a [`RwLock`].
+///
+/// This is returned by [`RwLock::conflicting_lock`] and [`RwLock::holder`].
+/// When several locks conflict, only one of them is reported.
+///
+/// [`RwLock`]: crate::RwLock
+/// [`RwLock::conflicting_lock`]: crate::RwLock::conflicting_lock
+/// [`RwLock::holder`]: crate::RwLock::holder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    mode: LockMode,
+    range: ByteRange,
+    pid: Option<u32>,
+}
+
+impl LockHolder {
+    pub(crate) fn new(mode: LockMode, range: ByteRange, pid: Option<u32>) -> Self {
+        Self { mode, range, pid }
+    }
+
+    /// Returns the kind of access the conflicting lock grants.
+    #[inline]
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    /// Returns the offset of the first byte the conflicting lock covers.
+    #[inline]
+    pub fn start(&self) -> u64 {
+        self.range.start
+    }
+
+    /// Returns the offset just past the last byte the conflicting lock
+    /// covers, or `None` if it covers bytes appended later as well.
+    #[inline]
+    pub fn end(&self) -> Option<u64> {
+        (self.range.end < MAX_END).then_some(self.range.end)
+    }
+
+    /// Returns the PID of the process which holds the conflicting lock.
+    ///
+    /// This is `None` if the operating system doesn't say. OFD locks belong
+    /// to an open file description rather than to a process, so they only
+    /// report a PID when held by the current process.
+    #[inline]
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            LockMode::Shared => "shared",
+            LockMode::Exclusive => "exclusive",
+        };
+        match self.end() {
+            None if self.start() == 0 => write!(f, "{mode} lock on the whole file")?,
+            None => write!(f, "{mode} lock on bytes {}..", self.start())?,
+            Some(end) => write!(f, "{mode} lock on bytes {}..{end}", self.start())?,
+        }
+        if let Some(pid) = self.pid {
+            write!(f, " held by process {pid}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,8 @@
+/// The kind of access a lock grants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    /// Shared read access.
+    Shared,
+    /// Exclusive write access.
+    Exclusive,
+}
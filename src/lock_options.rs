@@ -0,0 +1,358 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error;
+use crate::lock_mode::LockMode;
+use crate::read_guard::RwLockReadGuard;
+use crate::rw_lock::RwLock;
+use crate::sys;
+use crate::write_guard::RwLockWriteGuard;
+
+/// The environment variable which overrides [`LockOptions::max_wait`].
+const TIMEOUT_VAR: &str = "FD_LOCK_TIMEOUT";
+
+/// The environment variable which overrides [`LockOptions::retries`].
+const RETRIES_VAR: &str = "FD_LOCK_RETRIES";
+
+/// Options to configure how a [`RwLock`] is acquired.
+///
+/// This is created by [`LockOptions::new`] or [`RwLock::options`].
+///
+/// By default a single non-blocking attempt is made, like
+/// [`RwLock::try_read`] and [`RwLock::try_write`]. Contended attempts can be
+/// retried a number of times or until a maximum wait passes, spinning first
+/// and then sleeping with exponential backoff. [`LockOptions::read`] takes
+/// the lock with shared access and [`LockOptions::write`] with exclusive
+/// access.
+///
+/// Operators can tune the options without rebuilding through environment
+/// variables, which take precedence over the values set in code:
+///
+/// - `FD_LOCK_TIMEOUT` sets the maximum wait, either in seconds or with a
+///   `ms`, `s` or `m` suffix, like `250ms` or `1.5s`.
+/// - `FD_LOCK_RETRIES` sets the number of retries.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::{LockOptions, RwLock};
+/// use std::fs::File;
+/// use std::time::Duration;
+///
+/// fn main() -> std::io::Result<()> {
+///     let mut f = RwLock::new(File::open("foo.txt")?);
+///     let guard = LockOptions::new()
+///         .max_wait(Duration::from_secs(5))
+///         .jitter(true)
+///         .write(&mut f)?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LockOptions {
+    blocking: bool,
+    retries: Option<u32>,
+    max_wait: Option<Duration>,
+    spins: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    jitter: bool,
+    env: bool,
+}
+
+impl LockOptions {
+    /// Creates the default options: a single non-blocking attempt.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            blocking: false,
+            retries: None,
+            max_wait: None,
+            spins: 0,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(50),
+            jitter: false,
+            env: true,
+        }
+    }
+
+    /// Sets whether to wait for the lock in the kernel instead of retrying
+    /// non-blocking attempts. Defaults to `false`.
+    ///
+    /// A blocking acquisition waits until the maximum wait passes, or
+    /// forever if none is set. The retry settings don't apply to it.
+    #[inline]
+    pub fn blocking(&mut self, blocking: bool) -> &mut Self {
+        self.blocking = blocking;
+        self
+    }
+
+    /// Sets how many times a contended attempt is retried.
+    ///
+    /// Without a limit, attempts are retried until the maximum wait passes,
+    /// or not at all if no maximum wait is set either.
+    #[inline]
+    pub fn retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Sets how long to keep trying before giving up with an
+    /// `ErrorKind::TimedOut` error.
+    ///
+    /// Blocking acquisitions then wait like [`RwLock::read_timeout`], so on
+    /// Linux and Android they may install a `SIGURG` handler; see the [crate
+    /// documentation](crate#signals).
+    #[inline]
+    pub fn max_wait(&mut self, max_wait: Duration) -> &mut Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Sets how many retries spin instead of sleeping, before the backoff
+    /// starts. Defaults to `0`.
+    ///
+    /// Spinning only yields the thread, so it suits locks which are held
+    /// very briefly.
+    #[inline]
+    pub fn spins(&mut self, spins: u32) -> &mut Self {
+        self.spins = spins;
+        self
+    }
+
+    /// Sets the first and the longest pause between two retries. Each pause
+    /// doubles the previous one. Defaults to 1 millisecond and 50
+    /// milliseconds.
+    #[inline]
+    pub fn backoff(&mut self, initial: Duration, max: Duration) -> &mut Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Sets whether pauses are shortened by a random amount of up to half,
+    /// so processes contending for a lock don't retry in lockstep. Defaults
+    /// to `false`.
+    #[inline]
+    pub fn jitter(&mut self, jitter: bool) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets whether `FD_LOCK_TIMEOUT` and `FD_LOCK_RETRIES` override these
+    /// options. Defaults to `true`.
+    #[inline]
+    pub fn env_overrides(&mut self, env: bool) -> &mut Self {
+        self.env = env;
+        self
+    }
+
+    /// Locks `lock` with shared read access using these options.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is still contended after the last retry an
+    /// `ErrorKind::WouldBlock` error is returned, and once the maximum wait
+    /// passed an `ErrorKind::TimedOut` error. An invalid environment
+    /// override is reported as an `ErrorKind::InvalidInput` error.
+    pub fn read<'lock, T: sys::AsOpenFile>(
+        &self,
+        lock: &'lock RwLock<T>,
+    ) -> io::Result<RwLockReadGuard<'lock, T>> {
+        self.acquire(lock, LockMode::Shared)?;
+        let guard = sys::RwLockReadGuard::new(&lock.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks `lock` with exclusive write access using these options.
+    ///
+    /// # Errors
+    ///
+    /// See [`LockOptions::read`].
+    pub fn write<'lock, T: sys::AsOpenFile>(
+        &self,
+        lock: &'lock mut RwLock<T>,
+    ) -> io::Result<RwLockWriteGuard<'lock, T>> {
+        self.acquire(lock, LockMode::Exclusive)?;
+        let guard = sys::RwLockWriteGuard::new(&mut lock.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    fn acquire<T: sys::AsOpenFile>(&self, lock: &RwLock<T>, mode: LockMode) -> io::Result<()> {
+        let options = self.with_env()?;
+        // A maximum wait too long to represent never passes.
+        let deadline = options
+            .max_wait
+            .and_then(|max_wait| Instant::now().checked_add(max_wait));
+        if options.blocking {
+            return match deadline {
+                Some(deadline) => lock.lock_until(mode, sys::WHOLE, deadline),
+                None => lock.lock.lock(mode, sys::WHOLE, true),
+            };
+        }
+
+        let retries = match (options.retries, options.max_wait) {
+            (Some(retries), _) => retries,
+            (None, Some(_)) => u32::MAX,
+            (None, None) => 0,
+        };
+        let mut backoff = options.initial_backoff;
+        for attempt in 0.. {
+            let err = match lock.try_lock(mode, sys::WHOLE) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => err,
+                result => return result,
+            };
+            if attempt >= retries {
+                return Err(err);
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                return Err(error::timed_out());
+            }
+            if attempt < options.spins {
+                std::hint::spin_loop();
+                thread::yield_now();
+                continue;
+            }
+            let mut pause = options.pause(backoff);
+            if let Some(deadline) = deadline {
+                pause = pause.min(deadline - now);
+            }
+            thread::sleep(pause);
+            backoff = backoff.saturating_mul(2).min(options.max_backoff);
+        }
+        unreachable!()
+    }
+
+    /// Applies the environment overrides, if enabled.
+    fn with_env(&self) -> io::Result<Self> {
+        let mut options = self.clone();
+        if !self.env {
+            return Ok(options);
+        }
+        if let Some(value) = env_var(TIMEOUT_VAR)? {
+            let max_wait = parse_duration(&value).ok_or_else(|| invalid_var(TIMEOUT_VAR))?;
+            options.max_wait = Some(max_wait);
+        }
+        if let Some(value) = env_var(RETRIES_VAR)? {
+            let retries = value.trim().parse().map_err(|_| invalid_var(RETRIES_VAR))?;
+            options.retries = Some(retries);
+        }
+        Ok(options)
+    }
+
+    /// Returns the pause before the next retry, with jitter if enabled.
+    fn pause(&self, backoff: Duration) -> Duration {
+        if !self.jitter {
+            return backoff;
+        }
+        // Seeded randomly for every hasher, which is all the randomness this
+        // needs.
+        let random = RandomState::new().build_hasher().finish();
+        let cut = backoff.as_nanos() as u64 / 2;
+        let cut = random.checked_rem(cut + 1).unwrap_or(0);
+        backoff - Duration::from_nanos(cut)
+    }
+}
+
+impl Default for LockOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads an environment variable, treating an empty one as unset.
+fn env_var(name: &str) -> io::Result<Option<String>> {
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(std::env::VarError::NotUnicode(_)) => Err(invalid_var(name)),
+    }
+}
+
+fn invalid_var(name: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("invalid value for `{name}`"),
+    )
+}
+
+/// Parses a duration like `250ms`, `1.5s`, `2m` or `10`, in seconds.
+fn parse_duration(value: &str) -> Option<Duration> {
+    // `ms` has to be tried before `s`.
+    const UNITS: [(&str, f64); 3] = [("ms", 0.001), ("s", 1.0), ("m", 60.0)];
+
+    let value = value.trim();
+    let (number, scale) = UNITS
+        .iter()
+        .find_map(|&(unit, scale)| Some((value.strip_suffix(unit)?, scale)))
+        .unwrap_or((value, 1.0));
+    let number: f64 = number.trim().parse().ok()?;
+    Duration::try_from_secs_f64(number * scale).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_with_units() {
+        let parse = |value| parse_duration(value);
+        assert_eq!(parse("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse("2s"), Some(Duration::from_secs(2)));
+        assert_eq!(parse("2m"), Some(Duration::from_secs(120)));
+        assert_eq!(parse(" 10 "), Some(Duration::from_secs(10)));
+        assert_eq!(parse("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse("1e3"), Some(Duration::from_secs(1000)));
+        assert_eq!(parse("1e3ms"), Some(Duration::from_secs(1)));
+        assert_eq!(parse("0"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        for value in [
+            "", "s", "-1", "-1s", "inf", "infs", "nan", "NaN", "1e400", "5h", "x",
+        ] {
+            assert_eq!(parse_duration(value), None, "{value:?}");
+        }
+    }
+
+    // The variables are shared by the whole process, so they are only
+    // changed by this test.
+    #[test]
+    fn env_overrides() {
+        let options = LockOptions::new();
+
+        std::env::set_var(TIMEOUT_VAR, "  ");
+        std::env::set_var(RETRIES_VAR, "");
+        let with_env = options.with_env().unwrap();
+        assert_eq!(with_env.max_wait, None);
+        assert_eq!(with_env.retries, None);
+
+        std::env::set_var(TIMEOUT_VAR, "250ms");
+        std::env::set_var(RETRIES_VAR, " 3 ");
+        let with_env = options.with_env().unwrap();
+        assert_eq!(with_env.max_wait, Some(Duration::from_millis(250)));
+        assert_eq!(with_env.retries, Some(3));
+
+        std::env::set_var(TIMEOUT_VAR, "-1s");
+        let err = options.with_env().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        std::env::remove_var(TIMEOUT_VAR);
+        std::env::set_var(RETRIES_VAR, "many");
+        let err = options.with_env().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // Overrides can be turned off, invalid ones included.
+        let with_env = options.clone().env_overrides(false).with_env().unwrap();
+        assert_eq!(with_env.retries, None);
+        std::env::remove_var(RETRIES_VAR);
+    }
+}
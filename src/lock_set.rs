@@ -0,0 +1,233 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::lock_file::LockFileOptions;
+use crate::lock_mode::LockMode;
+use crate::rw_lock::RwLock;
+use crate::sys;
+use crate::unlock;
+
+/// A set of locks which are acquired together.
+///
+/// Acquiring several locks one by one deadlocks when two jobs take the same
+/// locks in a different order. A `LockSet` always takes its locks in the
+/// same global order, sorted by the device and inode of their files (the
+/// volume and file index on Windows), whatever order they were added in.
+/// A file added more than once, through the same lock or through different
+/// ones, is locked once, exclusively if any of the entries asks for it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::{LockMode, LockSet, RwLock};
+/// use std::fs::File;
+///
+/// fn main() -> std::io::Result<()> {
+///     let index = RwLock::new(File::open("index")?);
+///     let mut set = LockSet::new();
+///     set.add(&index, LockMode::Shared);
+///     set.add_path("objects.pack", LockMode::Exclusive)?;
+///     let guard = set.lock()?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LockSet<'lock, T: sys::AsOpenFile = File> {
+    entries: Vec<(Entry<'lock, T>, LockMode)>,
+}
+
+#[derive(Debug)]
+enum Entry<'lock, T: sys::AsOpenFile> {
+    Borrowed(&'lock RwLock<T>),
+    Owned(RwLock<T>),
+}
+
+impl<T: sys::AsOpenFile> Entry<'_, T> {
+    fn lock(&self) -> &RwLock<T> {
+        match self {
+            Entry::Borrowed(lock) => lock,
+            Entry::Owned(lock) => lock,
+        }
+    }
+}
+
+impl<'lock, T: sys::AsOpenFile> LockSet<'lock, T> {
+    /// Creates an empty set.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds `lock` to the set, to be acquired in `mode`.
+    #[inline]
+    pub fn add(&mut self, lock: &'lock RwLock<T>, mode: LockMode) -> &mut Self {
+        self.entries.push((Entry::Borrowed(lock), mode));
+        self
+    }
+
+    /// Adds `lock` to the set, to be acquired in `mode`. The set keeps it
+    /// open until it is dropped.
+    #[inline]
+    pub fn add_owned(&mut self, lock: RwLock<T>, mode: LockMode) -> &mut Self {
+        self.entries.push((Entry::Owned(lock), mode));
+        self
+    }
+
+    /// Returns the number of entries in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the set has no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Acquires every lock of the set, blocking the current thread until
+    /// all of them are held.
+    ///
+    /// # Errors
+    ///
+    /// If acquiring a lock fails, the locks acquired before it are released
+    /// and the error is returned.
+    pub fn lock(&self) -> io::Result<LockSetGuard<'_, 'lock, T>> {
+        self.acquire(true)
+    }
+
+    /// Attempts to acquire every lock of the set, without blocking.
+    ///
+    /// This is all or nothing: if any lock is held elsewhere, the locks
+    /// acquired before it are released again.
+    ///
+    /// # Errors
+    ///
+    /// If any lock is held elsewhere an `ErrorKind::WouldBlock` error is
+    /// returned. It describes the conflicting lock if enabled with
+    /// [`RwLock::set_describe_conflicts`].
+    pub fn try_lock(&self) -> io::Result<LockSetGuard<'_, 'lock, T>> {
+        self.acquire(false)
+    }
+
+    fn acquire(&self, blocking: bool) -> io::Result<LockSetGuard<'_, 'lock, T>> {
+        // Returning early drops the guard, which releases the locks acquired
+        // so far.
+        let mut guard = LockSetGuard {
+            set: self,
+            held: Vec::with_capacity(self.entries.len()),
+        };
+        for (index, mode) in self.order()? {
+            let lock = self.entries[index].0.lock();
+            if blocking {
+                lock.lock.lock(mode, sys::WHOLE, true)?;
+            } else {
+                lock.try_lock(mode, sys::WHOLE)?;
+            }
+            guard.held.push((index, mode));
+        }
+        Ok(guard)
+    }
+
+    /// Returns the entries to lock in the order to lock them in, one per
+    /// file, with the strongest mode any entry of the file asks for.
+    fn order(&self) -> io::Result<Vec<(usize, LockMode)>> {
+        let mut ids = Vec::with_capacity(self.entries.len());
+        for (index, (entry, mode)) in self.entries.iter().enumerate() {
+            ids.push((sys::file_id(&entry.lock().lock.inner)?, index, *mode));
+        }
+        ids.sort_by_key(|&(id, index, _)| (id, index));
+
+        let mut order: Vec<((u64, u64), usize, LockMode)> = Vec::with_capacity(ids.len());
+        for (id, index, mode) in ids {
+            match order.last_mut() {
+                Some((last, _, last_mode)) if *last == id => {
+                    if mode == LockMode::Exclusive {
+                        *last_mode = LockMode::Exclusive;
+                    }
+                }
+                _ => order.push((id, index, mode)),
+            }
+        }
+        Ok(order
+            .into_iter()
+            .map(|(_, index, mode)| (index, mode))
+            .collect())
+    }
+}
+
+impl LockSet<'_, File> {
+    /// Opens the file at `path` like [`LockFile::open`] and adds it to the
+    /// set, to be acquired in `mode`.
+    ///
+    /// [`LockFile::open`]: crate::LockFile::open
+    ///
+    /// # Errors
+    ///
+    /// If the file could not be opened the error is returned, naming the
+    /// path.
+    pub fn add_path(&mut self, path: impl AsRef<Path>, mode: LockMode) -> io::Result<&mut Self> {
+        let lock = LockFileOptions::new().open(path)?.into_inner();
+        Ok(self.add_owned(lock, mode))
+    }
+}
+
+impl<T: sys::AsOpenFile> Default for LockSet<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RAII structure used to release the locks of a [`LockSet`] when dropped.
+///
+/// This structure is created by [`LockSet::lock`] and
+/// [`LockSet::try_lock`].
+#[must_use = "if unused the LockSet will immediately unlock"]
+#[derive(Debug)]
+pub struct LockSetGuard<'set, 'lock, T: sys::AsOpenFile = File> {
+    set: &'set LockSet<'lock, T>,
+    held: Vec<(usize, LockMode)>,
+}
+
+impl<T: sys::AsOpenFile> LockSetGuard<'_, '_, T> {
+    /// Returns the underlying value of the entry at `index`, in the order
+    /// the entries were added in.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let (entry, _) = self.set.entries.get(index)?;
+        Some(&entry.lock().lock.inner)
+    }
+
+    /// Releases every lock, returning the first error if unlocking any of
+    /// them fails.
+    ///
+    /// Dropping the guard releases them as well, but reports errors only to
+    /// the hook registered with [`set_unlock_hook`].
+    ///
+    /// [`set_unlock_hook`]: crate::set_unlock_hook
+    pub fn unlock(mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for (index, mode) in self.held.drain(..).rev() {
+            let lock = self.set.entries[index].0.lock();
+            let released = lock.lock.release(mode, sys::WHOLE);
+            if result.is_ok() {
+                result = released;
+            }
+        }
+        result
+    }
+}
+
+impl<T: sys::AsOpenFile> Drop for LockSetGuard<'_, '_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        for (index, mode) in self.held.drain(..).rev() {
+            let lock = self.set.entries[index].0.lock();
+            unlock::report(lock.lock.release(mode, sys::WHOLE));
+        }
+    }
+}
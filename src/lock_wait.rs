@@ -0,0 +1,51 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::lock_holder::LockHolder;
+
+/// Progress of an acquisition which is waiting for a contended lock.
+///
+/// This is passed to the callback of [`RwLock::read_with_progress`] and
+/// [`RwLock::write_with_progress`]. It displays as a message like the one
+/// Cargo prints while waiting for a lock.
+///
+/// [`RwLock::read_with_progress`]: crate::RwLock::read_with_progress
+/// [`RwLock::write_with_progress`]: crate::RwLock::write_with_progress
+#[derive(Debug, Clone)]
+pub struct LockWait {
+    holder: Option<LockHolder>,
+    elapsed: Duration,
+}
+
+impl LockWait {
+    pub(crate) fn new(holder: Option<LockHolder>, elapsed: Duration) -> Self {
+        Self { holder, elapsed }
+    }
+
+    /// Returns a lock which stands in the way, if it could be determined.
+    ///
+    /// See [`RwLock::conflicting_lock`] for which locks can be reported.
+    ///
+    /// [`RwLock::conflicting_lock`]: crate::RwLock::conflicting_lock
+    #[inline]
+    pub fn holder(&self) -> Option<&LockHolder> {
+        self.holder.as_ref()
+    }
+
+    /// Returns how long the acquisition has been waiting. This is zero the
+    /// first time the callback is called.
+    #[inline]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl fmt::Display for LockWait {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Blocking waiting for file lock")?;
+        if let Some(holder) = &self.holder {
+            write!(f, " ({holder})")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::lock_file::LockFileOptions;
+use crate::rw_lock::RwLock;
+
+/// The longest file name a sanitized name is shortened to, leaving room for
+/// the hash and the extension.
+const MAX_NAME_LEN: usize = 200;
+
+/// Opens the lock named `name` in [`NamedScope::User`], creating its file if
+/// it doesn't exist yet.
+///
+/// Processes of the same user which use the same name get the same lock
+/// without agreeing on a path. Use [`NamedScope::open`] to share it with
+/// other users as well. Like [`LockFile`], the file is opened for reading and
+/// writing and never truncated.
+///
+/// [`LockFile`]: crate::LockFile
+///
+/// # Examples
+///
+/// ```no_run
+/// fn main() -> std::io::Result<()> {
+///     let mut lock = fd_lock::named("my-app-migrations")?;
+///     let guard = lock.write()?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// See [`NamedScope::open`].
+#[inline]
+pub fn named(name: &str) -> io::Result<RwLock<File>> {
+    NamedScope::User.open(name)
+}
+
+/// Returns the path of the file behind the lock named `name` in
+/// [`NamedScope::User`], creating its directory if needed.
+///
+/// # Errors
+///
+/// See [`NamedScope::path`].
+#[inline]
+pub fn named_path(name: &str) -> io::Result<PathBuf> {
+    NamedScope::User.path(name)
+}
+
+/// Which processes share a named lock.
+///
+/// Processes only share a lock if they resolve its name to the same
+/// directory. See each scope for which directory that is.
+///
+/// Characters other than ASCII letters, digits, `-`, `_` and `.` are
+/// replaced in the file name, as is a leading `.`. A hash of the original
+/// name is appended when anything is replaced, so names which differ only
+/// in those characters get different files.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NamedScope {
+    /// Shared by the processes of the current user.
+    ///
+    /// On Unix the files are kept in `$XDG_RUNTIME_DIR/fd-lock`, if that
+    /// variable names a directory owned by the current user. Otherwise they
+    /// are kept in `/tmp/fd-lock-<uid>`, whatever `TMPDIR` says, which has
+    /// some limits:
+    ///
+    /// - Processes with `$XDG_RUNTIME_DIR` and processes without it, such as
+    ///   some cron jobs, don't share locks.
+    /// - Services with a private `/tmp`, such as systemd units with
+    ///   `PrivateTmp=yes`, don't share locks with anyone else.
+    /// - Cleaners like `systemd-tmpfiles` may remove a file which hasn't
+    ///   been modified for a while, even while it is locked. The next
+    ///   process then locks a new file, beside the one still holding the
+    ///   old one.
+    /// - The directory name is predictable, so another user can create it
+    ///   first. Opening a lock then fails with
+    ///   `ErrorKind::PermissionDenied`.
+    ///
+    /// On Windows the files are kept in `fd-lock` in the temporary directory
+    /// of the user.
+    #[default]
+    User,
+    /// Shared by every process of the machine, whichever user it runs as.
+    ///
+    /// The files are kept in `/run/lock`, or in `/var/lock` where
+    /// `/run/lock` doesn't exist. They are created readable and writable by
+    /// every user, whatever the umask, so any of them can hold the lock.
+    /// An existing file is opened without trying to create it, which
+    /// `fs.protected_regular` forbids in those directories for files of other
+    /// users. This scope isn't supported on Windows.
+    System,
+}
+
+impl NamedScope {
+    /// Opens the lock named `name` in this scope, creating its file if it
+    /// doesn't exist yet.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::NamedScope;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut lock = NamedScope::System.open("my-app-migrations")?;
+    ///     let guard = lock.write()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`NamedScope::path`]. If the file could not be opened the error
+    /// is returned, naming the path.
+    pub fn open(self, name: &str) -> io::Result<RwLock<File>> {
+        let path = self.path(name)?;
+        let mut options = LockFileOptions::new();
+        #[cfg(unix)]
+        options.nofollow(true);
+        #[cfg(unix)]
+        if self == NamedScope::System {
+            return open_shared(&path, &mut options);
+        }
+        Ok(options.open(path)?.into_inner())
+    }
+
+    /// Returns the path of the file behind the lock named `name` in this
+    /// scope, creating its directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// If `name` is empty an `ErrorKind::InvalidInput` error is returned. If
+    /// the directory of [`NamedScope::User`] belongs to another user an
+    /// `ErrorKind::PermissionDenied` error is returned. If the directory of
+    /// [`NamedScope::System`] doesn't exist an `ErrorKind::NotFound` error is
+    /// returned, and on Windows an `ErrorKind::Unsupported` one.
+    pub fn path(self, name: &str) -> io::Result<PathBuf> {
+        let file = file_name(name)?;
+        Ok(self.dir()?.join(file))
+    }
+
+    /// Finds the directory the locks of this scope are kept in.
+    #[cfg(unix)]
+    fn dir(self) -> io::Result<PathBuf> {
+        match self {
+            NamedScope::User => {
+                if let Some(dir) = runtime_dir() {
+                    return private_dir(&dir.join("fd-lock"));
+                }
+                // Not `std::env::temp_dir`, which depends on `TMPDIR`.
+                let tmp = Path::new("/tmp");
+                let tmp = match tmp.is_dir() {
+                    true => tmp.to_owned(),
+                    false => std::env::temp_dir(),
+                };
+                private_dir(&tmp.join(format!("fd-lock-{}", uid())))
+            }
+            NamedScope::System => ["/run/lock", "/var/lock"]
+                .into_iter()
+                .map(PathBuf::from)
+                .find(|dir| dir.is_dir())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        ErrorKind::NotFound,
+                        "neither `/run/lock` nor `/var/lock` exists",
+                    )
+                }),
+        }
+    }
+
+    /// Finds the directory the locks of this scope are kept in.
+    #[cfg(not(unix))]
+    fn dir(self) -> io::Result<PathBuf> {
+        match self {
+            NamedScope::User => private_dir(&std::env::temp_dir().join("fd-lock")),
+            NamedScope::System => Err(io::Error::new(
+                ErrorKind::Unsupported,
+                "system-wide named locks are not supported on this platform",
+            )),
+        }
+    }
+}
+
+/// Turns a lock name into a file name which is safe to join to a directory.
+pub(crate) fn file_name(name: &str) -> io::Result<String> {
+    if name.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "the lock name is empty",
+        ));
+    }
+
+    let mut file: String = name
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            '.' if i == 0 => '_',
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    if file != name {
+        file.push_str(&format!("-{:016x}", fnv1a(name.as_bytes())));
+    }
+    file.push_str(".lock");
+    Ok(file)
+}
+
+/// A hash which is the same in every process and every build, unlike the
+/// hashers of the standard library.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Creates a directory only its owner can access, or checks that an
+/// existing one belongs to the current user.
+fn private_dir(dir: &Path) -> io::Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+
+        builder.mode(0o700);
+    }
+    match builder.create(dir) {
+        Ok(()) => return Ok(dir.to_owned()),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err),
+    }
+
+    // Don't follow a symbolic link someone else put in place.
+    let metadata = std::fs::symlink_metadata(dir)?;
+    #[cfg(unix)]
+    let owned = {
+        use std::os::unix::fs::MetadataExt;
+
+        metadata.uid() == uid()
+    };
+    #[cfg(not(unix))]
+    let owned = true;
+    if !metadata.is_dir() || !owned {
+        let msg = format!("`{}` is not a directory of the current user", dir.display());
+        return Err(io::Error::new(ErrorKind::PermissionDenied, msg));
+    }
+    Ok(dir.to_owned())
+}
+
+/// Returns `$XDG_RUNTIME_DIR`, if it is a directory of the current user.
+#[cfg(unix)]
+fn runtime_dir() -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = PathBuf::from(std::env::var_os("XDG_RUNTIME_DIR")?);
+    let metadata = std::fs::metadata(&dir).ok()?;
+    let owned = dir.is_absolute() && metadata.is_dir() && metadata.uid() == uid();
+    owned.then_some(dir)
+}
+
+/// Opens a lock file of [`NamedScope::System`], creating it readable and
+/// writable by every user if it doesn't exist.
+///
+/// With `fs.protected_regular`, opening with `O_CREAT` a file another user
+/// owns in a sticky directory fails, so existing files are opened without
+/// it.
+#[cfg(unix)]
+fn open_shared(path: &Path, options: &mut LockFileOptions) -> io::Result<RwLock<File>> {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    loop {
+        match options.create(false).create_new(false).open(path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            result => return Ok(result?.into_inner()),
+        }
+        match options.create_new(true).open(path) {
+            Ok(file) => {
+                // The umask may have restricted the mode.
+                let lock = file.into_inner();
+                lock.lock
+                    .inner
+                    .set_permissions(Permissions::from_mode(0o666))?;
+                return Ok(lock);
+            }
+            // Another process created it in the meantime.
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+    // SAFETY: always safe to call.
+    unsafe { libc::getuid() }
+}
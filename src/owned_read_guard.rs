@@ -0,0 +1,69 @@
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use std::{ops, ptr};
+
+use crate::lock_mode::LockMode;
+use crate::rw_lock::RwLock;
+use crate::sys;
+use crate::unlock::{self, UnlockError};
+
+/// Owned RAII structure used to release the shared read access of a lock when
+/// dropped.
+///
+/// Unlike [`RwLockReadGuard`], this guard keeps its lock alive through an
+/// [`Arc`] rather than borrowing it, so it can be stored next to the lock or
+/// moved into another thread.
+///
+/// This structure is created by the [`read_owned`] and [`try_read_owned`]
+/// methods on [`RwLock`].
+///
+/// [`RwLockReadGuard`]: crate::RwLockReadGuard
+/// [`read_owned`]: crate::RwLock::read_owned
+/// [`try_read_owned`]: crate::RwLock::try_read_owned
+/// [`RwLock`]: crate::RwLock
+#[must_use = "if unused the RwLock will immediately unlock"]
+#[derive(Debug)]
+pub struct OwnedRwLockReadGuard<T: sys::AsOpenFile> {
+    lock: Arc<RwLock<T>>,
+}
+
+impl<T: sys::AsOpenFile> OwnedRwLockReadGuard<T> {
+    pub(crate) fn new(lock: Arc<RwLock<T>>) -> Self {
+        Self { lock }
+    }
+
+    /// Releases the shared read access, returning the `Arc` it was taken
+    /// from.
+    ///
+    /// # Errors
+    ///
+    /// If unlocking fails the guard is returned with the error, still holding
+    /// the lock, so unlocking can be retried.
+    #[inline]
+    pub fn unlock(self) -> Result<Arc<RwLock<T>>, UnlockError<Self>> {
+        if let Err(err) = self.lock.lock.unlock(LockMode::Shared, sys::WHOLE) {
+            return Err(UnlockError::new(self, err));
+        }
+        let guard = ManuallyDrop::new(self);
+        // SAFETY: `guard` is never used or dropped again, so the lock is
+        // moved out of it exactly once.
+        Ok(unsafe { ptr::read(&guard.lock) })
+    }
+}
+
+impl<T: sys::AsOpenFile> ops::Deref for OwnedRwLockReadGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.lock.lock.inner
+    }
+}
+
+/// Release the lock.
+impl<T: sys::AsOpenFile> Drop for OwnedRwLockReadGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unlock::report(self.lock.lock.release(LockMode::Shared, sys::WHOLE));
+    }
+}
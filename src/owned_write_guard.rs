@@ -0,0 +1,114 @@
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+use std::{ops, ptr};
+
+use crate::lock_mode::LockMode;
+use crate::rw_lock::RwLock;
+use crate::sys;
+use crate::unlock::{self, UnlockError};
+
+/// Owned RAII structure used to release the exclusive write access of a lock
+/// when dropped.
+///
+/// Unlike [`RwLockWriteGuard`], this guard doesn't borrow its lock, so it can
+/// be stored next to the lock or moved into another thread. It comes in two
+/// flavors:
+///
+/// - [`write_owned`] and [`try_write_owned`] keep the lock alive through an
+///   [`Arc`]. Other clones of the `Arc` may still hold read guards on byte
+///   ranges outside the locked region, so this flavor only dereferences to
+///   `&T`. For files that is enough: `&File` implements `Read`, `Write` and
+///   `Seek`.
+/// - [`into_write_guard`] takes the lock by value. This flavor also
+///   dereferences to `&mut T`, and [`unlock`] gives the lock back.
+///
+/// [`RwLockWriteGuard`]: crate::RwLockWriteGuard
+/// [`write_owned`]: crate::RwLock::write_owned
+/// [`try_write_owned`]: crate::RwLock::try_write_owned
+/// [`into_write_guard`]: crate::RwLock::into_write_guard
+/// [`unlock`]: OwnedRwLockWriteGuard::unlock
+#[must_use = "if unused the RwLock will immediately unlock"]
+#[derive(Debug)]
+pub struct OwnedRwLockWriteGuard<T: sys::AsOpenFile, L: Borrow<RwLock<T>> = Arc<RwLock<T>>> {
+    lock: L,
+    _inner: PhantomData<T>,
+}
+
+impl<T: sys::AsOpenFile, L: Borrow<RwLock<T>>> OwnedRwLockWriteGuard<T, L> {
+    pub(crate) fn new(lock: L) -> Self {
+        Self {
+            lock,
+            _inner: PhantomData,
+        }
+    }
+
+    /// Releases the lock, returning the `RwLock` or `Arc` it was taken from.
+    ///
+    /// Dropping the guard releases it as well, but has no way to report an
+    /// error other than the hook registered with [`set_unlock_hook`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let mut guard = f.into_write_guard()?;
+    ///     write!(guard, "chashu cat")?;
+    ///     let f = guard.unlock()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If unlocking fails the guard is returned with the error, still holding
+    /// the lock, so unlocking can be retried.
+    ///
+    /// [`set_unlock_hook`]: crate::set_unlock_hook
+    #[inline]
+    pub fn unlock(self) -> Result<L, UnlockError<Self>> {
+        let result = self
+            .lock
+            .borrow()
+            .lock
+            .unlock(LockMode::Exclusive, sys::WHOLE);
+        if let Err(err) = result {
+            return Err(UnlockError::new(self, err));
+        }
+        let guard = ManuallyDrop::new(self);
+        // SAFETY: `guard` is never used or dropped again, so the lock is
+        // moved out of it exactly once.
+        Ok(unsafe { ptr::read(&guard.lock) })
+    }
+}
+
+impl<T: sys::AsOpenFile, L: Borrow<RwLock<T>>> ops::Deref for OwnedRwLockWriteGuard<T, L> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.lock.borrow().lock.inner
+    }
+}
+
+impl<T: sys::AsOpenFile> ops::DerefMut for OwnedRwLockWriteGuard<T, RwLock<T>> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.lock.lock.inner
+    }
+}
+
+/// Release the lock.
+impl<T: sys::AsOpenFile, L: Borrow<RwLock<T>>> Drop for OwnedRwLockWriteGuard<T, L> {
+    #[inline]
+    fn drop(&mut self) {
+        let lock = self.lock.borrow();
+        unlock::report(lock.lock.release(LockMode::Exclusive, sys::WHOLE));
+    }
+}
@@ -0,0 +1,372 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::liveness::Liveness;
+use crate::lock_file::LockFileOptions;
+use crate::owned_write_guard::OwnedRwLockWriteGuard;
+use crate::rw_lock::RwLock;
+use crate::sys;
+
+/// A PID file which keeps a daemon single-instance.
+///
+/// Creating a `Pidfile` takes an exclusive lock on the file without
+/// blocking, then replaces its contents with the PID of the current
+/// process. The lock is held until the `Pidfile` is dropped, or the process
+/// exits. Another process trying to create the same `Pidfile` meanwhile gets
+/// an `ErrorKind::WouldBlock` error, and can look up who holds it with
+/// [`Pidfile::holder`].
+///
+/// The first line of the file is the PID, so it can also be used by tools
+/// which expect a classic PID file. The file is left in place when the lock
+/// is released: only the lock tells whether the daemon is running.
+///
+/// On Windows, locks are mandatory. Other processes can't read a PID file
+/// while it is held, so [`Pidfile::holder`] fails there.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::Pidfile;
+/// use std::io::ErrorKind;
+///
+/// fn main() -> std::io::Result<()> {
+///     let pidfile = match Pidfile::create("/run/chashu.pid") {
+///         Ok(pidfile) => pidfile,
+///         Err(err) if err.kind() == ErrorKind::WouldBlock => {
+///             if let Some(holder) = Pidfile::holder("/run/chashu.pid")? {
+///                 eprintln!("already running as process {}", holder.pid());
+///             }
+///             std::process::exit(1);
+///         }
+///         Err(err) => return Err(err),
+///     };
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Pidfile {
+    guard: OwnedRwLockWriteGuard<File>,
+    path: PathBuf,
+}
+
+impl Pidfile {
+    /// Locks the PID file at `path` and writes the PID of the current
+    /// process into it.
+    ///
+    /// # Errors
+    ///
+    /// If another process holds the PID file an `ErrorKind::WouldBlock` error
+    /// is returned.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        PidfileOptions::new().create(path)
+    }
+
+    /// Returns a builder to configure what is written to the PID file.
+    #[inline]
+    pub fn options() -> PidfileOptions {
+        PidfileOptions::new()
+    }
+
+    /// Reads who holds the PID file at `path`.
+    ///
+    /// Returns `None` if the file doesn't exist or is empty, which happens
+    /// while its holder is still writing it. The file isn't locked to read
+    /// it, so the holder may have exited since.
+    ///
+    /// # Errors
+    ///
+    /// If the file doesn't hold a PID an `ErrorKind::InvalidData` error is
+    /// returned.
+    pub fn holder(path: impl AsRef<Path>) -> io::Result<Option<PidfileHolder>> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => PidfileHolder::parse(&contents),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns whether the PID file at `path` was left behind by a holder
+    /// which is no longer running, so it can be removed.
+    ///
+    /// A PID file is stale when nobody holds its lock and the process it
+    /// names isn't known to be running, as checked by
+    /// [`PidfileHolder::liveness`]. A missing PID file isn't stale. Another
+    /// process may take the PID file right after this returns, so a daemon
+    /// should take it over with [`Pidfile::create`] rather than remove it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::Pidfile;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     if Pidfile::is_stale("/run/chashu.pid")? {
+    ///         eprintln!("taking over from a daemon which didn't shut down cleanly");
+    ///     }
+    ///     let pidfile = Pidfile::create("/run/chashu.pid")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn is_stale(path: impl AsRef<Path>) -> io::Result<bool> {
+        let path = path.as_ref();
+        let lock = match File::open(path) {
+            Ok(file) => RwLock::new(file),
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        let _guard = match lock.try_read() {
+            Ok(guard) => guard,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        Ok(match Pidfile::holder(path)? {
+            Some(holder) => holder.liveness() != Liveness::Alive,
+            None => true,
+        })
+    }
+
+    /// Returns the path of the PID file.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Releases the PID file, returning the error if unlocking fails.
+    ///
+    /// Dropping the `Pidfile` releases it as well.
+    #[inline]
+    pub fn unlock(self) -> io::Result<()> {
+        self.guard.unlock()?;
+        Ok(())
+    }
+}
+
+/// Options to configure what a [`Pidfile`] contains.
+///
+/// This is created by [`Pidfile::options`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::Pidfile;
+///
+/// fn main() -> std::io::Result<()> {
+///     let pidfile = Pidfile::options()
+///         .hostname(true)
+///         .start_time(true)
+///         .create("/run/chashu.pid")?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PidfileOptions {
+    hostname: bool,
+    start_time: bool,
+    file: LockFileOptions,
+}
+
+impl PidfileOptions {
+    /// Creates the default options: write only the PID.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            hostname: false,
+            start_time: false,
+            file: LockFileOptions::new(),
+        }
+    }
+
+    /// Sets whether the hostname of the machine is written after the PID.
+    /// Defaults to `false`.
+    #[inline]
+    pub fn hostname(&mut self, hostname: bool) -> &mut Self {
+        self.hostname = hostname;
+        self
+    }
+
+    /// Sets whether the time the PID file was taken is written after the
+    /// PID. Defaults to `false`.
+    ///
+    /// On Linux this writes the PID namespace of the process as well. Both
+    /// let [`PidfileHolder::liveness`] tell the holder apart from a later
+    /// process which reused its PID.
+    #[inline]
+    pub fn start_time(&mut self, start_time: bool) -> &mut Self {
+        self.start_time = start_time;
+        self
+    }
+
+    /// Sets the options the file is opened with.
+    #[inline]
+    pub fn file_options(&mut self, options: LockFileOptions) -> &mut Self {
+        self.file = options;
+        self
+    }
+
+    /// Locks the PID file at `path` and writes the PID of the current
+    /// process into it.
+    ///
+    /// # Errors
+    ///
+    /// If another process holds the PID file an `ErrorKind::WouldBlock` error
+    /// is returned.
+    pub fn create(&self, path: impl AsRef<Path>) -> io::Result<Pidfile> {
+        let path = path.as_ref();
+        let lock = Arc::new(self.file.open(path)?.into_inner());
+        let guard = lock
+            .try_write_owned()
+            .map_err(|err| held_error(path, err))?;
+
+        let holder = PidfileHolder {
+            pid: std::process::id(),
+            hostname: self.hostname.then(hostname).transpose()?,
+            started: self.start_time.then(SystemTime::now),
+            pid_namespace: self.start_time.then(sys::pid_namespace).flatten(),
+        };
+        guard.set_len(0)?;
+        (&*guard).write_all(holder.to_string().as_bytes())?;
+
+        Ok(Pidfile {
+            guard,
+            path: path.to_owned(),
+        })
+    }
+}
+
+impl Default for PidfileOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The process which holds a [`Pidfile`], as written in the file.
+///
+/// This is returned by [`Pidfile::holder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PidfileHolder {
+    pid: u32,
+    hostname: Option<String>,
+    started: Option<SystemTime>,
+    pid_namespace: Option<u64>,
+}
+
+impl PidfileHolder {
+    /// Returns the PID of the holder.
+    #[inline]
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns the hostname of the machine the holder runs on, if it was
+    /// written.
+    #[inline]
+    pub fn hostname(&self) -> Option<&str> {
+        self.hostname.as_deref()
+    }
+
+    /// Returns when the holder took the PID file, with a precision of one
+    /// second, if it was written.
+    #[inline]
+    pub fn started(&self) -> Option<SystemTime> {
+        self.started
+    }
+
+    /// Checks whether the holder is still running.
+    ///
+    /// This uses [`Liveness::of_process`] with the start time, if it was
+    /// written. A holder on another machine, or in another PID namespace,
+    /// can't be checked and is reported as [`Liveness::Unknown`].
+    pub fn liveness(&self) -> Liveness {
+        if let Some(recorded) = &self.hostname {
+            if hostname().ok().as_ref() != Some(recorded) {
+                return Liveness::Unknown;
+            }
+        }
+        if self.pid_namespace.is_some() && self.pid_namespace != sys::pid_namespace() {
+            return Liveness::Unknown;
+        }
+        Liveness::of_process(self.pid, self.started)
+    }
+
+    /// Parses the contents of a PID file: the PID on the first line, then
+    /// optional `hostname` and `started` lines.
+    fn parse(contents: &str) -> io::Result<Option<Self>> {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "malformed PID file");
+        let mut lines = contents.lines();
+        let pid = match lines.next().map(str::trim) {
+            None | Some("") => return Ok(None),
+            Some(pid) => pid.parse().map_err(|_| invalid())?,
+        };
+
+        let mut holder = PidfileHolder {
+            pid,
+            hostname: None,
+            started: None,
+            pid_namespace: None,
+        };
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("hostname", hostname)) => holder.hostname = Some(hostname.to_owned()),
+                Some(("started", secs)) => {
+                    let secs = secs.parse().map_err(|_| invalid())?;
+                    holder.started = Some(UNIX_EPOCH + Duration::from_secs(secs));
+                }
+                Some(("pidns", ino)) => {
+                    holder.pid_namespace = Some(ino.parse().map_err(|_| invalid())?);
+                }
+                // Leave room for fields added later.
+                _ => {}
+            }
+        }
+        Ok(Some(holder))
+    }
+}
+
+impl std::fmt::Display for PidfileHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.pid)?;
+        if let Some(hostname) = &self.hostname {
+            writeln!(f, "hostname {hostname}")?;
+        }
+        if let Some(started) = self.started {
+            let secs = started.duration_since(UNIX_EPOCH).unwrap_or_default();
+            writeln!(f, "started {}", secs.as_secs())?;
+        }
+        if let Some(ino) = self.pid_namespace {
+            writeln!(f, "pidns {ino}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Names the holder of the PID file in a `WouldBlock` error, if it can be
+/// read, and notes if it is no longer running.
+fn held_error(path: &Path, err: io::Error) -> io::Error {
+    match (err.kind(), Pidfile::holder(path)) {
+        (ErrorKind::WouldBlock, Ok(Some(holder))) => {
+            let mut msg = format!("`{}` is held by process {}", path.display(), holder.pid);
+            if holder.liveness() == Liveness::Dead {
+                msg.push_str(", which is no longer running");
+            }
+            io::Error::new(ErrorKind::WouldBlock, msg)
+        }
+        _ => err,
+    }
+}
+
+/// Returns the hostname of this machine.
+fn hostname() -> io::Result<String> {
+    #[cfg(unix)]
+    return Ok(rustix::system::uname()
+        .nodename()
+        .to_string_lossy()
+        .into_owned());
+
+    #[cfg(windows)]
+    return std::env::var("COMPUTERNAME").map_err(|err| io::Error::new(ErrorKind::NotFound, err));
+}
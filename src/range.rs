@@ -0,0 +1,85 @@
+use std::io::{self, ErrorKind};
+use std::ops::{Bound, RangeBounds};
+
+/// The end of unbounded ranges.
+///
+/// This is far beyond any offset a real file reaches, so such ranges cover
+/// bytes appended later. The offsets from here on are reserved for the locks
+/// the crate uses for its own bookkeeping.
+pub(crate) const MAX_END: u64 = i64::MAX as u64 - 1;
+
+/// The byte which serializes upgradable readers.
+pub(crate) const UPGRADE: ByteRange = ByteRange {
+    start: MAX_END,
+    end: MAX_END + 1,
+};
+
+/// A non-empty range of bytes in a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ByteRange {
+    pub(crate) start: u64,
+    pub(crate) end: u64,
+}
+
+impl ByteRange {
+    /// Convert a range of offsets, rejecting empty ranges and ranges which
+    /// reach into the offsets reserved from [`MAX_END`] on.
+    pub(crate) fn new(range: impl RangeBounds<u64>) -> io::Result<Self> {
+        let invalid = || io::Error::new(ErrorKind::InvalidInput, "invalid byte range");
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).ok_or_else(invalid)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => MAX_END,
+        };
+        match start < end && end <= MAX_END {
+            true => Ok(ByteRange { start, end }),
+            false => Err(invalid()),
+        }
+    }
+
+    /// The number of bytes in the range.
+    #[cfg_attr(unix, allow(dead_code))]
+    pub(crate) fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Whether `other` lies entirely within this range.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn contains(&self, other: &ByteRange) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Whether this range and `other` share at least one byte.
+    #[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+    pub(crate) fn overlaps(&self, other: &ByteRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// The parts of this range not covered by any of `others`.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn subtract(&self, others: &[ByteRange]) -> Vec<ByteRange> {
+        let mut rest = vec![*self];
+        for other in others {
+            rest = rest
+                .into_iter()
+                .flat_map(|range| {
+                    let below = ByteRange {
+                        start: range.start,
+                        end: range.end.min(other.start),
+                    };
+                    let above = ByteRange {
+                        start: range.start.max(other.end),
+                        end: range.end,
+                    };
+                    [below, above].into_iter().filter(|r| r.start < r.end)
+                })
+                .collect();
+        }
+        rest
+    }
+}
@@ -1,15 +1,18 @@
-use std::ops;
+use std::{mem, ops};
 
 use crate::sys;
+use crate::unlock::UnlockError;
 
 /// RAII structure used to release the shared read access of a lock when
 /// dropped.
 ///
-/// This structure is created by the [`read`] and [`try_read`] methods on
-/// [`RwLock`].
+/// This structure is created by the [`read`], [`try_read`], [`read_range`]
+/// and [`try_read_range`] methods on [`RwLock`].
 ///
 /// [`read`]: crate::RwLock::read
 /// [`try_read`]: crate::RwLock::try_read
+/// [`read_range`]: crate::RwLock::read_range
+/// [`try_read_range`]: crate::RwLock::try_read_range
 /// [`RwLock`]: crate::RwLock
 #[must_use = "if unused the RwLock will immediately unlock"]
 #[derive(Debug)]
@@ -21,6 +24,43 @@ impl<'lock, T: sys::AsOpenFile> RwLockReadGuard<'lock, T> {
     pub(crate) fn new(guard: sys::RwLockReadGuard<'lock, T>) -> Self {
         Self { guard }
     }
+
+    /// Releases the shared read access, returning the error if unlocking
+    /// fails.
+    ///
+    /// Dropping the guard releases it as well, but has no way to report an
+    /// error other than the hook registered with [`set_unlock_hook`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.read()?;
+    ///     guard.unlock()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If unlocking fails the guard is returned with the error, still holding
+    /// the lock, so unlocking can be retried.
+    ///
+    /// [`set_unlock_hook`]: crate::set_unlock_hook
+    #[inline]
+    pub fn unlock(self) -> Result<(), UnlockError<Self>> {
+        match self.guard.unlock() {
+            Ok(()) => {
+                mem::forget(self);
+                Ok(())
+            }
+            Err(err) => Err(UnlockError::new(self, err)),
+        }
+    }
 }
 
 impl<T: sys::AsOpenFile> ops::Deref for RwLockReadGuard<'_, T> {
@@ -31,9 +71,3 @@ impl<T: sys::AsOpenFile> ops::Deref for RwLockReadGuard<'_, T> {
         self.guard.deref()
     }
 }
-
-/// Release the lock.
-impl<T: sys::AsOpenFile> Drop for RwLockReadGuard<'_, T> {
-    #[inline]
-    fn drop(&mut self) {}
-}
@@ -1,7 +1,32 @@
+use crate::cancel_token::CancelToken;
+use crate::error;
+#[cfg(feature = "async")]
+use crate::held::Held;
+use crate::interrupt_policy::InterruptPolicy;
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::lock_options::LockOptions;
+use crate::lock_wait::LockWait;
+use crate::owned_read_guard::OwnedRwLockReadGuard;
+use crate::owned_write_guard::OwnedRwLockWriteGuard;
+use crate::range::{ByteRange, UPGRADE};
 use crate::read_guard::RwLockReadGuard;
 use crate::sys;
+use crate::unlock;
+use crate::upgradable_read_guard::RwLockUpgradableReadGuard;
 use crate::write_guard::RwLockWriteGuard;
-use std::io;
+use crate::Backend;
+use std::io::{self, ErrorKind};
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The longest pause between two attempts of a timed acquisition.
+const MAX_BACKOFF: Duration = Duration::from_millis(50);
+
+/// The shortest interval between two progress reports.
+const MIN_PROGRESS_INTERVAL: Duration = Duration::from_millis(10);
 
 /// Advisory reader-writer lock for files.
 ///
@@ -11,7 +36,8 @@ use std::io;
 /// allows for read-only access (shared access).
 #[derive(Debug)]
 pub struct RwLock<T: sys::AsOpenFile> {
-    lock: sys::RwLock<T>,
+    pub(crate) lock: sys::RwLock<T>,
+    describe_conflicts: bool,
 }
 
 impl<T: sys::AsOpenFile> RwLock<T> {
@@ -32,9 +58,146 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     pub fn new(inner: T) -> Self {
         Self {
             lock: sys::RwLock::new(inner),
+            describe_conflicts: false,
         }
     }
 
+    /// Create a new instance which locks the file using the given [`Backend`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::{Backend, RwLock};
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     # #[cfg(target_os = "linux")]
+    ///     let mut f = RwLock::with_backend(File::open("foo.txt")?, Backend::Ofd);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn with_backend(inner: T, backend: Backend) -> Self {
+        Self {
+            lock: sys::RwLock::with_backend(inner, backend),
+            describe_conflicts: false,
+        }
+    }
+
+    /// Returns the backend this lock uses.
+    ///
+    /// This is the backend passed to [`RwLock::with_backend`], unless the
+    /// operating system did not support it and the lock fell back to
+    /// [`Backend::Native`].
+    #[inline]
+    pub fn backend(&self) -> Backend {
+        self.lock.backend()
+    }
+
+    /// Sets whether `ErrorKind::WouldBlock` errors describe the lock which
+    /// caused them. Defaults to `false`.
+    ///
+    /// When enabled, a non-blocking acquisition which fails looks up the
+    /// conflicting lock with [`RwLock::conflicting_lock`], so the error
+    /// reads like "the file is locked: exclusive lock on the whole file held
+    /// by process 1234". The error kind stays the same. If the lookup fails
+    /// the original error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     f.set_describe_conflicts(true);
+    ///     if let Err(err) = f.try_write() {
+    ///         eprintln!("{err}");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_describe_conflicts(&mut self, describe: bool) {
+        self.describe_conflicts = describe;
+    }
+
+    /// Sets what blocking acquisitions do when a signal interrupts them.
+    /// Defaults to [`InterruptPolicy::Return`].
+    ///
+    /// This applies to every method which waits for the lock, including
+    /// upgrades and downgrades of guards, but not to the timed methods,
+    /// which keep waiting until their deadline. [`RwLock::read_with`] and
+    /// [`RwLock::write_with`] override it for a single call.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::{InterruptPolicy, RwLock};
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     f.set_interrupt_policy(InterruptPolicy::Retry);
+    ///     let guard = f.write()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn set_interrupt_policy(&mut self, policy: InterruptPolicy) {
+        self.lock.set_interrupt_policy(policy);
+    }
+
+    /// Returns a lock which keeps this lock from being acquired in `mode`,
+    /// or `None` if none was found.
+    ///
+    /// Reports the kind of the conflicting lock, the bytes it covers and the
+    /// PID of its holder where the operating system exposes them. Locks held
+    /// through this `RwLock` are reported as well, with the PID of the
+    /// current process. The answer may be stale by the time it is returned.
+    ///
+    /// `None` doesn't prove the lock is free. On Linux `flock(2)` locks are
+    /// looked up in `/proc/locks` by device and inode number, which some file
+    /// systems report differently from `fstat`. The device of the mount is
+    /// tried as well, which covers btrfs subvolumes, but on file systems such
+    /// as overlayfs a held lock may not be found. Use [`RwLock::try_write`]
+    /// to find out whether the lock can be taken.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::{LockMode, RwLock};
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     if let Some(holder) = f.conflicting_lock(LockMode::Shared)? {
+    ///         println!("waiting for the {holder}");
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an `ErrorKind::Unsupported` error where the lock holder can't
+    /// be queried. Linux supports it for every backend, by reading
+    /// `/proc/locks` for `flock(2)` locks; other platforms don't.
+    #[inline]
+    pub fn conflicting_lock(&self, mode: LockMode) -> io::Result<Option<LockHolder>> {
+        self.lock.conflicting_lock(mode, sys::WHOLE)
+    }
+
+    /// Returns a lock held on this file, or `None` if none was found.
+    ///
+    /// This is the lock which keeps [`RwLock::write`] from proceeding. See
+    /// [`RwLock::conflicting_lock`] for details.
+    #[inline]
+    pub fn holder(&self) -> io::Result<Option<LockHolder>> {
+        self.conflicting_lock(LockMode::Exclusive)
+    }
+
     /// Locks this lock with shared read access, blocking the current thread
     /// until it can be acquired.
     ///
@@ -45,12 +208,15 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     /// acquire the lock first.
     ///
     /// Returns an RAII guard which will release this thread's shared access
-    /// once it is dropped.
+    /// once it is dropped. Several read guards may be taken from the same
+    /// `RwLock`; the underlying file stays locked until the last of them is
+    /// dropped.
     ///
     /// # Errors
     ///
     /// On Unix this may return an `ErrorKind::Interrupted` if the operation was
-    /// interrupted by a signal handler.
+    /// interrupted by a signal handler, unless the lock is set to retry with
+    /// [`RwLock::set_interrupt_policy`].
     #[inline]
     pub fn read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
         let guard = self.lock.read()?;
@@ -75,10 +241,33 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     /// interrupted by a signal handler.
     #[inline]
     pub fn try_read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
-        let guard = self.lock.try_read()?;
+        self.try_lock(LockMode::Shared, sys::WHOLE)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
         Ok(RwLockReadGuard::new(guard))
     }
 
+    /// Returns the default [`LockOptions`], to configure how this lock is
+    /// retried or waited for. Pass the lock to [`LockOptions::read`] or
+    /// [`LockOptions::write`] to acquire it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.options().retries(3).write(&mut f)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn options(&self) -> LockOptions {
+        LockOptions::new()
+    }
+
     /// Locks this lock with exclusive write access, blocking the current thread
     /// until it can be acquired.
     ///
@@ -91,13 +280,106 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     /// # Errors
     ///
     /// On Unix this may return an `ErrorKind::Interrupted` if the operation was
-    /// interrupted by a signal handler.
+    /// interrupted by a signal handler, unless the lock is set to retry with
+    /// [`RwLock::set_interrupt_policy`].
     #[inline]
     pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
         let guard = self.lock.write()?;
         Ok(RwLockWriteGuard::new(guard))
     }
 
+    /// Locks this lock with shared read access, handling signals with the
+    /// given policy instead of the lock's own one.
+    ///
+    /// This behaves like [`RwLock::read`]. Pass [`InterruptPolicy::Return`]
+    /// to let a signal cancel the wait on a lock which otherwise retries.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::{InterruptPolicy, RwLock};
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.read_with(InterruptPolicy::Retry)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn read_with(&self, policy: InterruptPolicy) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock.lock_with(LockMode::Shared, sys::WHOLE, policy)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks this lock with exclusive write access, handling signals with
+    /// the given policy instead of the lock's own one.
+    ///
+    /// This behaves like [`RwLock::write`]. See [`RwLock::read_with`] for
+    /// details.
+    #[inline]
+    pub fn write_with(&mut self, policy: InterruptPolicy) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock
+            .lock_with(LockMode::Exclusive, sys::WHOLE, policy)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Locks this lock with shared read access, blocking the current thread
+    /// until it can be acquired or `token` is cancelled.
+    ///
+    /// This behaves like [`RwLock::read`], but another thread can make it
+    /// give up by calling [`CancelToken::cancel`]. Signals which don't come
+    /// from the token are retried. On Unix this may install a `SIGURG`
+    /// handler; see the [crate documentation](crate#signals).
+    ///
+    /// # Errors
+    ///
+    /// If the token is cancelled before the lock is acquired an error is
+    /// returned which [`Error::from`] classifies as [`Error::Cancelled`].
+    /// Nothing is left locked.
+    ///
+    /// [`Error::from`]: crate::Error
+    /// [`Error::Cancelled`]: crate::Error::Cancelled
+    #[inline]
+    pub fn read_cancellable(&self, token: &CancelToken) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock
+            .lock_cancellable(LockMode::Shared, sys::WHOLE, token)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks this lock with exclusive write access, blocking the current
+    /// thread until it can be acquired or `token` is cancelled.
+    ///
+    /// This behaves like [`RwLock::write`]. See [`RwLock::read_cancellable`]
+    /// and [`CancelToken`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::{CancelToken, RwLock};
+    /// use std::fs::File;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let token = CancelToken::new();
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.write_cancellable(&token)?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_cancellable(
+        &mut self,
+        token: &CancelToken,
+    ) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock
+            .lock_cancellable(LockMode::Exclusive, sys::WHOLE, token)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
     /// Attempts to lock this lock with exclusive write access.
     ///
     /// If the lock could not be acquired at this time, then `Err` is returned.
@@ -111,7 +393,550 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     /// interrupted by a signal handler.
     #[inline]
     pub fn try_write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
-        let guard = self.lock.try_write()?;
+        self.try_lock(LockMode::Exclusive, sys::WHOLE)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Locks this lock with shared read access through an [`Arc`], blocking
+    /// the current thread until it can be acquired.
+    ///
+    /// This behaves like [`RwLock::read`], but the returned guard keeps the
+    /// lock alive instead of borrowing it, so it can be moved into another
+    /// thread or stored next to the lock.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::sync::Arc;
+    /// use std::thread;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = Arc::new(RwLock::new(File::open("foo.txt")?));
+    ///     let guard = f.clone().read_owned()?;
+    ///     thread::spawn(move || drop(guard)).join().unwrap();
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn read_owned(self: Arc<Self>) -> io::Result<OwnedRwLockReadGuard<T>> {
+        self.lock.lock(LockMode::Shared, sys::WHOLE, true)?;
+        Ok(OwnedRwLockReadGuard::new(self))
+    }
+
+    /// Attempts to acquire this lock with shared read access through an
+    /// [`Arc`].
+    ///
+    /// See [`RwLock::read_owned`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is already held and `ErrorKind::WouldBlock` error is returned.
+    #[inline]
+    pub fn try_read_owned(self: Arc<Self>) -> io::Result<OwnedRwLockReadGuard<T>> {
+        self.try_lock(LockMode::Shared, sys::WHOLE)?;
+        Ok(OwnedRwLockReadGuard::new(self))
+    }
+
+    /// Locks this lock with exclusive write access through an [`Arc`],
+    /// blocking the current thread until it can be acquired.
+    ///
+    /// This behaves like [`RwLock::write`], but the returned guard keeps the
+    /// lock alive instead of borrowing it. Other clones of the `Arc` can still
+    /// reach the lock, so the guard only gives shared access to the file; see
+    /// [`OwnedRwLockWriteGuard`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    /// use std::sync::Arc;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = Arc::new(RwLock::new(File::open("foo.txt")?));
+    ///     let guard = f.clone().write_owned()?;
+    ///     write!(&*guard, "chashu cat")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn write_owned(self: Arc<Self>) -> io::Result<OwnedRwLockWriteGuard<T>> {
+        self.lock.lock(LockMode::Exclusive, sys::WHOLE, true)?;
+        Ok(OwnedRwLockWriteGuard::new(self))
+    }
+
+    /// Attempts to lock this lock with exclusive write access through an
+    /// [`Arc`].
+    ///
+    /// See [`RwLock::write_owned`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is already held and `ErrorKind::WouldBlock` error is returned.
+    #[inline]
+    pub fn try_write_owned(self: Arc<Self>) -> io::Result<OwnedRwLockWriteGuard<T>> {
+        self.try_lock(LockMode::Exclusive, sys::WHOLE)?;
+        Ok(OwnedRwLockWriteGuard::new(self))
+    }
+
+    /// Consumes this lock and locks it with exclusive write access, blocking
+    /// the current thread until it can be acquired.
+    ///
+    /// The returned guard owns the lock, so it has no lifetime attached to
+    /// it. [`OwnedRwLockWriteGuard::unlock`] releases the lock and gives it
+    /// back.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let mut guard = f.into_write_guard()?;
+    ///     write!(guard, "chashu cat")?;
+    ///     let f = guard.unlock()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be acquired the error is returned and the lock
+    /// is dropped.
+    #[inline]
+    pub fn into_write_guard(self) -> io::Result<OwnedRwLockWriteGuard<T, Self>> {
+        self.lock.lock(LockMode::Exclusive, sys::WHOLE, true)?;
+        Ok(OwnedRwLockWriteGuard::new(self))
+    }
+
+    /// Locks this lock with upgradable read access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// An upgradable reader shares the file with plain readers, but at most
+    /// one upgradable reader can hold the lock at a time, across all
+    /// processes. The returned guard can later be upgraded to exclusive
+    /// write access with [`RwLockUpgradableReadGuard::upgrade`] without
+    /// another upgradable reader getting in first. This makes it possible to
+    /// read a file, decide whether it needs changing, and only then write
+    /// it.
+    ///
+    /// Upgradable readers are serialized through a lock on a reserved byte
+    /// far beyond the end of the file, so this requires a backend which can
+    /// lock byte ranges: [`Backend::Ofd`] on Unix.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() -> std::io::Result<()> {
+    /// use fd_lock::{Backend, RwLock};
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// let mut f = RwLock::with_backend(File::open("foo.txt")?, Backend::Ofd);
+    /// let mut guard = f.upgradable_read()?;
+    /// let mut contents = String::new();
+    /// (&*guard).read_to_string(&mut contents)?;
+    /// if contents.is_empty() {
+    ///     let mut guard = guard.upgrade()?;
+    ///     write!(guard, "chashu cat")?;
+    /// }
+    /// # Ok(()) }
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An `ErrorKind::Unsupported` error is returned if the backend can't
+    /// lock byte ranges.
+    /// On Unix this may return an `ErrorKind::Interrupted` if the operation was
+    /// interrupted by a signal handler.
+    ///
+    /// [`Backend::Ofd`]: crate::Backend
+    #[inline]
+    pub fn upgradable_read(&mut self) -> io::Result<RwLockUpgradableReadGuard<'_, T>> {
+        self.lock_upgradable(true)?;
+        Ok(RwLockUpgradableReadGuard::new(&mut self.lock))
+    }
+
+    /// Attempts to acquire this lock with upgradable read access.
+    ///
+    /// See [`RwLock::upgradable_read`] for details. This function does not
+    /// block.
+    ///
+    /// # Errors
+    ///
+    /// If the lock is held by a writer or another upgradable reader an
+    /// `ErrorKind::WouldBlock` error is returned. Backends which can't lock
+    /// byte ranges return `ErrorKind::Unsupported`.
+    #[inline]
+    pub fn try_upgradable_read(&mut self) -> io::Result<RwLockUpgradableReadGuard<'_, T>> {
+        self.lock_upgradable(false)?;
+        Ok(RwLockUpgradableReadGuard::new(&mut self.lock))
+    }
+
+    /// Locks this lock with shared read access, blocking the current thread
+    /// until it can be acquired or the timeout expires.
+    ///
+    /// This behaves like [`RwLock::read`], but gives up once `timeout` has
+    /// passed. On Linux and Android the thread waits in the kernel, and a
+    /// timer interrupts the wait once the timeout expires. Elsewhere the
+    /// thread sleeps between attempts while the lock is contended, backing
+    /// off exponentially up to a few tens of milliseconds.
+    ///
+    /// The timer interrupts the thread with `SIGURG`, and the first timed
+    /// wait installs a process-wide handler for it. See the [crate
+    /// documentation](crate#signals) for what this means for the rest of
+    /// the program.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.read_timeout(Duration::from_secs(5))?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be acquired before the timeout expired an
+    /// `ErrorKind::TimedOut` error is returned.
+    #[inline]
+    pub fn read_timeout(&self, timeout: Duration) -> io::Result<RwLockReadGuard<'_, T>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.read_deadline(deadline),
+            None => self.read(),
+        }
+    }
+
+    /// Locks this lock with shared read access, blocking the current thread
+    /// until it can be acquired or the deadline passes.
+    ///
+    /// See [`RwLock::read_timeout`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be acquired before the deadline an
+    /// `ErrorKind::TimedOut` error is returned.
+    #[inline]
+    pub fn read_deadline(&self, deadline: Instant) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock_until(LockMode::Shared, sys::WHOLE, deadline)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks this lock with exclusive write access, blocking the current
+    /// thread until it can be acquired or the timeout expires.
+    ///
+    /// This behaves like [`RwLock::write`], but gives up once `timeout` has
+    /// passed. On Linux and Android the thread waits in the kernel, and a
+    /// timer interrupts the wait once the timeout expires. Elsewhere the
+    /// thread sleeps between attempts while the lock is contended, backing
+    /// off exponentially up to a few tens of milliseconds.
+    ///
+    /// Like [`RwLock::read_timeout`], this may install a `SIGURG` handler;
+    /// see the [crate documentation](crate#signals).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     write!(f.write_timeout(Duration::from_secs(5))?, "chashu cat")?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be acquired before the timeout expired an
+    /// `ErrorKind::TimedOut` error is returned.
+    #[inline]
+    pub fn write_timeout(&mut self, timeout: Duration) -> io::Result<RwLockWriteGuard<'_, T>> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.write_deadline(deadline),
+            None => self.write(),
+        }
+    }
+
+    /// Locks this lock with exclusive write access, blocking the current
+    /// thread until it can be acquired or the deadline passes.
+    ///
+    /// See [`RwLock::write_timeout`] for details.
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be acquired before the deadline an
+    /// `ErrorKind::TimedOut` error is returned.
+    #[inline]
+    pub fn write_deadline(&mut self, deadline: Instant) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock_until(LockMode::Exclusive, sys::WHOLE, deadline)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Locks this lock with shared read access, blocking the current thread
+    /// until it can be acquired and reporting progress while it waits.
+    ///
+    /// If the lock is contended, `progress` is called right away, and then
+    /// again every `interval` for as long as the thread keeps waiting. The
+    /// [`LockWait`] it is passed includes the conflicting lock when the
+    /// platform can tell, so command line tools can explain what they are
+    /// waiting for instead of appearing frozen. If the lock isn't contended,
+    /// `progress` is never called. Intervals shorter than 10 milliseconds are
+    /// rounded up to 10 milliseconds.
+    ///
+    /// Each interval is waited like [`RwLock::read_timeout`] waits, so this
+    /// may install a `SIGURG` handler; see the [crate
+    /// documentation](crate#signals).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.read_with_progress(Duration::from_secs(1), |wait| {
+    ///         eprintln!("{wait}");
+    ///     })?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`RwLock::read`].
+    pub fn read_with_progress(
+        &self,
+        interval: Duration,
+        progress: impl FnMut(&LockWait),
+    ) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock_with_progress(LockMode::Shared, interval, progress)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks this lock with exclusive write access, blocking the current
+    /// thread until it can be acquired and reporting progress while it
+    /// waits.
+    ///
+    /// See [`RwLock::read_with_progress`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     let guard = f.write_with_progress(Duration::from_secs(1), |wait| {
+    ///         eprintln!("{wait}");
+    ///     })?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`RwLock::write`].
+    pub fn write_with_progress(
+        &mut self,
+        interval: Duration,
+        progress: impl FnMut(&LockWait),
+    ) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock_with_progress(LockMode::Exclusive, interval, progress)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Locks this lock with shared read access, waiting without blocking the
+    /// current thread until it can be acquired.
+    ///
+    /// The lock is acquired on the thread pool of the [`blocking`] crate, so
+    /// this works with any async runtime. Dropping the future before it
+    /// completes is safe: if the lock is acquired after the future was
+    /// dropped, it is released again right away.
+    ///
+    /// This method is only available with the `async` feature enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let f = RwLock::new(File::open("foo.txt")?);
+    /// let guard = f.read_async().await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// [`blocking`]: https://docs.rs/blocking
+    #[cfg(feature = "async")]
+    pub async fn read_async(&self) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock_async(LockMode::Shared, sys::WHOLE).await?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, sys::WHOLE);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks this lock with exclusive write access, waiting without blocking
+    /// the current thread until it can be acquired.
+    ///
+    /// See [`RwLock::read_async`] for details.
+    ///
+    /// This method is only available with the `async` feature enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// # async fn run() -> std::io::Result<()> {
+    /// let mut f = RwLock::new(File::open("foo.txt")?);
+    /// write!(f.write_async().await?, "chashu cat")?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn write_async(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock_async(LockMode::Exclusive, sys::WHOLE).await?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Locks a range of bytes with shared read access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// This behaves like [`RwLock::read`], except that only the given range
+    /// of the file is locked. Ranges may extend past the end of the file, and
+    /// an unbounded end covers any bytes appended later. Locks on ranges
+    /// which don't overlap never conflict.
+    ///
+    /// Whole-file locks and range locks only conflict where they overlap. On
+    /// Windows a whole-file lock covers only the first byte of the file.
+    ///
+    /// Offsets from `i64::MAX - 1` on are reserved: the crate locks the byte
+    /// at `i64::MAX - 1` to serialize upgradable readers. An unbounded end
+    /// stops right before it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(target_os = "linux")]
+    /// # fn main() -> std::io::Result<()> {
+    /// use fd_lock::{Backend, RwLock};
+    /// use std::fs::File;
+    ///
+    /// let f = RwLock::with_backend(File::open("foo.txt")?, Backend::Ofd);
+    /// let header = f.read_range(0..512)?;
+    /// # Ok(()) }
+    /// # #[cfg(not(target_os = "linux"))]
+    /// # fn main() {}
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// An `ErrorKind::InvalidInput` error is returned if the range is empty
+    /// or ends past `i64::MAX - 1`, and an `ErrorKind::Unsupported` error if the backend can't lock byte
+    /// ranges: on Unix this requires [`Backend::Ofd`].
+    /// On Unix this may return an `ErrorKind::Interrupted` if the operation was
+    /// interrupted by a signal handler.
+    ///
+    /// [`Backend::Ofd`]: crate::Backend
+    #[inline]
+    pub fn read_range(&self, range: impl RangeBounds<u64>) -> io::Result<RwLockReadGuard<'_, T>> {
+        let guard = self.lock.read_range(ByteRange::new(range)?)?;
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Attempts to lock a range of bytes with shared read access.
+    ///
+    /// This behaves like [`RwLock::try_read`], except that only the given
+    /// range of the file is locked. See [`RwLock::read_range`] for how ranges
+    /// are interpreted.
+    ///
+    /// # Errors
+    ///
+    /// If an overlapping range is already locked for writing an
+    /// `ErrorKind::WouldBlock` error is returned. Invalid ranges and backends
+    /// without byte-range support are reported like in [`RwLock::read_range`].
+    #[inline]
+    pub fn try_read_range(
+        &self,
+        range: impl RangeBounds<u64>,
+    ) -> io::Result<RwLockReadGuard<'_, T>> {
+        let range = ByteRange::new(range)?;
+        self.try_lock(LockMode::Shared, range)?;
+        let guard = sys::RwLockReadGuard::new(&self.lock, range);
+        Ok(RwLockReadGuard::new(guard))
+    }
+
+    /// Locks a range of bytes with exclusive write access, blocking the
+    /// current thread until it can be acquired.
+    ///
+    /// This behaves like [`RwLock::write`], except that only the given range
+    /// of the file is locked. See [`RwLock::read_range`] for how ranges are
+    /// interpreted.
+    ///
+    /// # Errors
+    ///
+    /// Invalid ranges and backends without byte-range support are reported
+    /// like in [`RwLock::read_range`].
+    /// On Unix this may return an `ErrorKind::Interrupted` if the operation was
+    /// interrupted by a signal handler.
+    #[inline]
+    pub fn write_range(
+        &mut self,
+        range: impl RangeBounds<u64>,
+    ) -> io::Result<RwLockWriteGuard<'_, T>> {
+        let guard = self.lock.write_range(ByteRange::new(range)?)?;
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Attempts to lock a range of bytes with exclusive write access.
+    ///
+    /// This behaves like [`RwLock::try_write`], except that only the given
+    /// range of the file is locked. See [`RwLock::read_range`] for how ranges
+    /// are interpreted.
+    ///
+    /// # Errors
+    ///
+    /// If an overlapping range is already locked an `ErrorKind::WouldBlock`
+    /// error is returned. Invalid ranges and backends without byte-range
+    /// support are reported like in [`RwLock::read_range`].
+    #[inline]
+    pub fn try_write_range(
+        &mut self,
+        range: impl RangeBounds<u64>,
+    ) -> io::Result<RwLockWriteGuard<'_, T>> {
+        let range = ByteRange::new(range)?;
+        self.try_lock(LockMode::Exclusive, range)?;
+        let guard = sys::RwLockWriteGuard::new(&mut self.lock, range);
         Ok(RwLockWriteGuard::new(guard))
     }
 
@@ -123,4 +948,114 @@ impl<T: sys::AsOpenFile> RwLock<T> {
     {
         self.lock.into_inner()
     }
+
+    /// Takes the upgrade byte and then a shared lock on the whole file.
+    fn lock_upgradable(&self, blocking: bool) -> io::Result<()> {
+        self.lock
+            .lock(LockMode::Exclusive, UPGRADE, blocking)
+            .map_err(|err| self.describe(err, LockMode::Exclusive, UPGRADE))?;
+        if let Err(err) = self.lock.lock(LockMode::Shared, sys::WHOLE, blocking) {
+            unlock::report(self.lock.release(LockMode::Exclusive, UPGRADE));
+            return Err(self.describe(err, LockMode::Shared, sys::WHOLE));
+        }
+        Ok(())
+    }
+
+    /// Locks a range without blocking, describing the conflicting lock in
+    /// the error if enabled.
+    pub(crate) fn try_lock(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        self.lock
+            .lock(mode, range, false)
+            .map_err(|err| self.describe(err, mode, range))
+    }
+
+    /// Adds the conflicting lock to a `WouldBlock` error, if enabled with
+    /// [`RwLock::set_describe_conflicts`].
+    fn describe(&self, err: io::Error, mode: LockMode, range: ByteRange) -> io::Error {
+        if !self.describe_conflicts || err.kind() != ErrorKind::WouldBlock {
+            return err;
+        }
+        match self.lock.conflicting_lock(mode, range) {
+            Ok(Some(holder)) => io::Error::new(
+                ErrorKind::WouldBlock,
+                format!("the file is locked: {holder}"),
+            ),
+            _ => err,
+        }
+    }
+
+    /// Acquires a range on the `blocking` thread pool.
+    ///
+    /// The worker locks through a duplicate of the file descriptor, so it
+    /// doesn't need to borrow `self` and can outlive the future.
+    #[cfg(feature = "async")]
+    async fn lock_async(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        let lock = self.lock.detach()?;
+        let held = blocking::unblock(move || Held::acquire(lock, mode, range)).await?;
+        held.adopt();
+        Ok(())
+    }
+
+    /// Acquires a range, giving up once the deadline passes.
+    ///
+    /// Where the kernel can be interrupted by a timer it waits for the lock
+    /// itself. Otherwise this retries a non-blocking acquisition, sleeping
+    /// with exponential backoff in between.
+    pub(crate) fn lock_until(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        deadline: Instant,
+    ) -> io::Result<()> {
+        if let Some(result) = self.lock.lock_until(mode, range, deadline) {
+            return result;
+        }
+
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            match self.lock.lock(mode, range, false) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(error::timed_out());
+            }
+            thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Acquires the whole file, calling `progress` once it is contended and
+    /// then every `interval` until it is acquired.
+    fn lock_with_progress(
+        &self,
+        mode: LockMode,
+        interval: Duration,
+        mut progress: impl FnMut(&LockWait),
+    ) -> io::Result<()> {
+        match self.lock.lock(mode, sys::WHOLE, false) {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+            result => return result,
+        }
+
+        let interval = interval.max(MIN_PROGRESS_INTERVAL);
+        let start = Instant::now();
+        let mut elapsed = Duration::ZERO;
+        loop {
+            let holder = self.lock.conflicting_lock(mode, sys::WHOLE).ok().flatten();
+            progress(&LockWait::new(holder, elapsed));
+
+            // An interval too long to represent never passes.
+            let Some(deadline) = Instant::now().checked_add(interval) else {
+                return self.lock.lock(mode, sys::WHOLE, true);
+            };
+            match self.lock_until(mode, sys::WHOLE, deadline) {
+                Err(err) if err.kind() == ErrorKind::TimedOut && Instant::now() >= deadline => {}
+                result => return result,
+            }
+            elapsed = start.elapsed();
+        }
+    }
 }
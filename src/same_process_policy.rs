@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The policy set with [`set_same_process_policy`].
+static POLICY: AtomicU8 = AtomicU8::new(0);
+
+/// What a lock does when another lock of the same process holds its file.
+///
+/// `flock(2)` and OFD locks belong to an open file description. If two
+/// parts of a program open the same file separately, their locks conflict
+/// like those of two processes, and a blocking acquisition through one of
+/// them waits forever for the lock the program already holds through the
+/// other. Set a policy with [`set_same_process_policy`] to have every lock
+/// of the process keep track of the files it holds, by device and inode, and
+/// catch this before it hangs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SameProcessPolicy {
+    /// Locks don't know about each other. Conflicting acquisitions block
+    /// like they would against another process.
+    #[default]
+    Ignore,
+    /// Blocking acquisitions fail with an `ErrorKind::Deadlock` error, which
+    /// [`Error::from`] classifies as [`Error::Deadlock`]. Non-blocking ones
+    /// fail with `ErrorKind::WouldBlock` as usual.
+    ///
+    /// [`Error::from`]: crate::Error
+    /// [`Error::Deadlock`]: crate::Error::Deadlock
+    Error,
+    /// Blocking acquisitions wait until the other lock of this process is
+    /// released, without blocking in the kernel meanwhile.
+    Wait,
+}
+
+/// Sets how locks handle conflicts with other locks of the same process.
+/// Defaults to [`SameProcessPolicy::Ignore`].
+///
+/// This applies to every lock of the process. Locks held while the policy
+/// was [`SameProcessPolicy::Ignore`] aren't tracked, so set it at startup,
+/// before taking any lock. It has no effect on Windows yet.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::{RwLock, SameProcessPolicy};
+/// use std::fs::File;
+///
+/// fn main() -> std::io::Result<()> {
+///     fd_lock::set_same_process_policy(SameProcessPolicy::Error);
+///     let mut a = RwLock::new(File::open("foo.txt")?);
+///     let mut b = RwLock::new(File::open("foo.txt")?);
+///     let _guard = a.write()?;
+///     assert!(b.write().is_err());
+///     Ok(())
+/// }
+/// ```
+#[inline]
+pub fn set_same_process_policy(policy: SameProcessPolicy) {
+    let value = match policy {
+        SameProcessPolicy::Ignore => 0,
+        SameProcessPolicy::Error => 1,
+        SameProcessPolicy::Wait => 2,
+    };
+    POLICY.store(value, Ordering::Relaxed);
+}
+
+/// Returns the policy set with [`set_same_process_policy`].
+#[cfg_attr(not(unix), allow(dead_code))]
+pub(crate) fn same_process_policy() -> SameProcessPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        1 => SameProcessPolicy::Error,
+        2 => SameProcessPolicy::Wait,
+        _ => SameProcessPolicy::Ignore,
+    }
+}
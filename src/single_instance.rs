@@ -0,0 +1,341 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::lock_file::LockFile;
+use crate::owned_write_guard::OwnedRwLockWriteGuard;
+
+/// How long a second instance waits for the first one to start listening,
+/// and then to take its arguments.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the running instance waits for a later one to send its
+/// arguments.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The size of the largest request, in bytes, including the lengths.
+const MAX_REQUEST_LEN: usize = 2 << 20;
+
+/// Sent back by the running instance once it has read a request.
+const ACK: u8 = 1;
+
+/// Keeps an application single-instance, forwarding the arguments of later
+/// launches to the running instance.
+///
+/// The first instance takes an exclusive lock on a lock file and listens on
+/// a Unix domain socket next to it, named after the lock file with `.sock`
+/// appended. A later instance finds the lock taken, connects to the socket
+/// and sends its arguments instead, and the running instance acknowledges
+/// them once it has read them. The socket is created with the process umask,
+/// so put the lock file in a directory only the intended users can access.
+///
+/// This type is only available on Unix.
+///
+/// # Examples
+///
+/// ```no_run
+/// use fd_lock::{Instance, SingleInstance};
+///
+/// fn main() -> std::io::Result<()> {
+///     let instance = match SingleInstance::acquire("/tmp/chashu.lock")? {
+///         Instance::Primary(instance) => instance,
+///         Instance::Forwarded => return Ok(()),
+///     };
+///     for args in instance.requests() {
+///         println!("launched again with {:?}", args?);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SingleInstance {
+    listener: UnixListener,
+    socket: PathBuf,
+    _guard: OwnedRwLockWriteGuard<File>,
+}
+
+/// The outcome of [`SingleInstance::acquire`].
+#[derive(Debug)]
+pub enum Instance {
+    /// This process is the first instance.
+    Primary(SingleInstance),
+    /// Another instance is running, and it received the arguments.
+    Forwarded,
+}
+
+impl SingleInstance {
+    /// Becomes the first instance, or forwards the arguments of this process
+    /// to the running one.
+    ///
+    /// # Errors
+    ///
+    /// If another instance holds the lock but doesn't acknowledge the
+    /// arguments within a few seconds an `ErrorKind::TimedOut` error is
+    /// returned. If it exits before the arguments could be sent, this tries
+    /// to become the first instance again. If it exits after they were sent
+    /// but before acknowledging them, it may have handled them, so an
+    /// `ErrorKind::UnexpectedEof` error is returned instead of sending them
+    /// twice.
+    #[inline]
+    pub fn acquire(path: impl AsRef<Path>) -> io::Result<Instance> {
+        Self::acquire_with_args(path, std::env::args_os())
+    }
+
+    /// Becomes the first instance, or forwards `args` to the running one.
+    ///
+    /// See [`SingleInstance::acquire`] for details.
+    pub fn acquire_with_args<I, S>(path: impl AsRef<Path>, args: I) -> io::Result<Instance>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let path = path.as_ref();
+        let socket = socket_path(path);
+        let lock = Arc::new(LockFile::open(path)?.into_inner());
+        let request = encode(args)?;
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            match lock.clone().try_write_owned() {
+                Ok(guard) => return Self::listen(socket, guard).map(Instance::Primary),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+
+            // The running instance may not be listening yet, or may just
+            // have exited, so keep trying both until the deadline.
+            match UnixStream::connect(&socket).and_then(|stream| send(stream, &request)) {
+                Ok(stream) => return acknowledged(stream).map(|()| Instance::Forwarded),
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) if err.kind() == ErrorKind::ConnectionRefused => {}
+                // It exited before reading the arguments.
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {}
+                Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+                Err(err) => return Err(err),
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(timed_out());
+            }
+            thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(Duration::from_millis(50));
+        }
+    }
+
+    /// Returns an iterator over the arguments forwarded by later instances,
+    /// blocking while waiting for the next one.
+    ///
+    /// Requests which can't be read, because the instance sending them
+    /// stalls or sends malformed data, are skipped. The iterator only
+    /// returns an error if accepting connections fails.
+    #[inline]
+    pub fn requests(&self) -> Requests<'_> {
+        Requests { instance: self }
+    }
+
+    /// Returns the socket later instances connect to, for use with an event
+    /// loop. Pass accepted connections to [`SingleInstance::read_request`].
+    #[inline]
+    pub fn listener(&self) -> &UnixListener {
+        &self.listener
+    }
+
+    /// Reads the arguments a later instance sent over `stream`, and
+    /// acknowledges them.
+    ///
+    /// # Errors
+    ///
+    /// If the request is malformed or larger than a few megabytes an
+    /// `ErrorKind::InvalidData` error is returned. Requests are read one at a
+    /// time, so a client which doesn't send its request within half a second
+    /// is dropped with an `ErrorKind::TimedOut` error.
+    pub fn read_request(&self, stream: UnixStream) -> io::Result<Vec<OsString>> {
+        // Connections accepted from a non-blocking listener may be
+        // non-blocking themselves.
+        stream.set_nonblocking(false)?;
+        let mut request = Request {
+            stream,
+            deadline: Instant::now() + REQUEST_TIMEOUT,
+            remaining: MAX_REQUEST_LEN,
+        };
+        let count = request.read_len()?;
+        let mut args = Vec::new();
+        for _ in 0..count {
+            let mut arg = vec![0; request.read_len()?];
+            request.read_exact(&mut arg)?;
+            args.push(OsString::from_vec(arg));
+        }
+        request.stream.write_all(&[ACK])?;
+        Ok(args)
+    }
+
+    /// Starts listening, now that this process holds the lock.
+    fn listen(socket: PathBuf, guard: OwnedRwLockWriteGuard<File>) -> io::Result<Self> {
+        // A socket left behind by an instance which crashed would make
+        // binding fail. Holding the lock, nobody else is using it.
+        match std::fs::remove_file(&socket) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        Ok(SingleInstance {
+            listener: UnixListener::bind(&socket)?,
+            socket,
+            _guard: guard,
+        })
+    }
+}
+
+/// Remove the socket while the lock is still held.
+impl Drop for SingleInstance {
+    #[inline]
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// An iterator over the arguments forwarded to a [`SingleInstance`].
+///
+/// This structure is created by [`SingleInstance::requests`].
+#[derive(Debug)]
+pub struct Requests<'a> {
+    instance: &'a SingleInstance,
+}
+
+impl Iterator for Requests<'_> {
+    type Item = io::Result<Vec<OsString>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (stream, _) = match self.instance.listener.accept() {
+                Ok(connection) => connection,
+                Err(err) => return Some(Err(err)),
+            };
+            // One misbehaving client must not stop the running instance.
+            if let Ok(args) = self.instance.read_request(stream) {
+                return Some(Ok(args));
+            }
+        }
+    }
+}
+
+/// Returns the path of the socket belonging to a lock file.
+fn socket_path(path: &Path) -> PathBuf {
+    let mut socket = path.as_os_str().to_owned();
+    socket.push(".sock");
+    socket.into()
+}
+
+/// Encodes a request: the number of arguments, then each argument prefixed
+/// with its length, all lengths as little-endian `u32`s.
+fn encode<I, S>(args: I) -> io::Result<Vec<u8>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let mut request = Vec::new();
+    request.extend_from_slice(&(args.len() as u32).to_le_bytes());
+    for arg in &args {
+        let arg = arg.as_ref().as_bytes();
+        request.extend_from_slice(&(arg.len() as u32).to_le_bytes());
+        request.extend_from_slice(arg);
+        if request.len() > MAX_REQUEST_LEN {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "arguments too long",
+            ));
+        }
+    }
+    Ok(request)
+}
+
+/// Sends a request to the running instance.
+fn send(mut stream: UnixStream, request: &[u8]) -> io::Result<UnixStream> {
+    stream.write_all(request)?;
+    Ok(stream)
+}
+
+/// Waits for the running instance to acknowledge a request it was sent.
+fn acknowledged(mut stream: UnixStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut ack = [0];
+    match stream.read_exact(&mut ack) {
+        Ok(()) if ack[0] == ACK => Ok(()),
+        Ok(()) => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "unexpected reply from the running instance",
+        )),
+        Err(err) if err.kind() == ErrorKind::WouldBlock => Err(timed_out()),
+        Err(err) if err.kind() == ErrorKind::ConnectionReset => Err(unacknowledged()),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Err(unacknowledged()),
+        Err(err) => Err(err),
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(
+        ErrorKind::TimedOut,
+        "timed out waiting for the running instance",
+    )
+}
+
+fn unacknowledged() -> io::Error {
+    io::Error::new(
+        ErrorKind::UnexpectedEof,
+        "the running instance exited without acknowledging the arguments",
+    )
+}
+
+/// A request being read, which has to arrive before a deadline and fit in
+/// a limited number of bytes.
+struct Request {
+    stream: UnixStream,
+    deadline: Instant,
+    remaining: usize,
+}
+
+impl Request {
+    fn read_len(&mut self) -> io::Result<usize> {
+        let mut len = [0; 4];
+        self.read_exact(&mut len)?;
+        match u32::from_le_bytes(len) as usize {
+            len if len <= self.remaining => Ok(len),
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "request too long")),
+        }
+    }
+
+    /// Reads exactly `buf.len()` bytes, giving up at the deadline however
+    /// slowly they trickle in.
+    fn read_exact(&mut self, mut buf: &mut [u8]) -> io::Result<()> {
+        if buf.len() > self.remaining {
+            return Err(io::Error::new(ErrorKind::InvalidData, "request too long"));
+        }
+        self.remaining -= buf.len();
+        while !buf.is_empty() {
+            let timeout = self.deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(io::Error::new(
+                    ErrorKind::TimedOut,
+                    "timed out reading the request",
+                ));
+            }
+            self.stream.set_read_timeout(Some(timeout))?;
+            match self.stream.read(buf) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => buf = &mut buf[n..],
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
@@ -5,11 +5,15 @@ cfg_if! {
         mod unix;
         pub use unix::*;
         pub(crate) use rustix::fd::AsFd as AsOpenFile;
+        #[allow(unused_imports)]
+        pub(crate) use rustix::fd::OwnedFd as OwnedOpenFile;
     } else if #[cfg(windows)] {
         mod windows;
         pub use windows::*;
         #[doc(no_inline)]
         pub(crate) use std::os::windows::io::AsHandle as AsOpenFile;
+        #[allow(unused_imports)]
+        pub(crate) use std::os::windows::io::OwnedHandle as OwnedOpenFile;
     } else {
         mod unsupported;
         pub use unsupported;
@@ -0,0 +1,138 @@
+use std::sync::OnceLock;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::time::{Duration, Instant};
+use std::{mem, ptr};
+
+/// The signal which wakes up cancelled threads. It is ignored by default,
+/// and few programs use it.
+const SIGNAL: libc::c_int = libc::SIGURG;
+
+/// How often a timer keeps interrupting its thread once the deadline passed,
+/// in case the first signal arrived before the thread blocked.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const TIMER_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A thread which can be interrupted by a signal.
+#[derive(Debug)]
+pub(crate) struct Thread(libc::pthread_t);
+
+// SAFETY: a `pthread_t` only identifies a thread, and may be used from any
+// other thread.
+unsafe impl Send for Thread {}
+unsafe impl Sync for Thread {}
+
+impl Thread {
+    /// Returns the current thread, making sure the signal interrupts its
+    /// system calls.
+    ///
+    /// Returns `None` if the signal doesn't interrupt system calls.
+    pub(crate) fn current() -> Option<Self> {
+        if !interrupts_syscalls() {
+            return None;
+        }
+        // SAFETY: always safe to call.
+        Some(Thread(unsafe { libc::pthread_self() }))
+    }
+
+    /// Interrupts the thread's current system call, if any.
+    ///
+    /// The thread must still be running.
+    pub(crate) fn interrupt(&self) {
+        // SAFETY: the thread is running, so its `pthread_t` is valid.
+        unsafe { libc::pthread_kill(self.0, SIGNAL) };
+    }
+}
+
+/// A timer which interrupts the thread which started it once a deadline
+/// passes, and then again every millisecond until it is dropped.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub(crate) struct Timer(libc::timer_t);
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Timer {
+    /// Starts a timer for the current thread.
+    ///
+    /// Returns `None` if the signal doesn't interrupt system calls, or the
+    /// timer could not be created.
+    pub(crate) fn start(deadline: Instant) -> Option<Self> {
+        if !interrupts_syscalls() {
+            return None;
+        }
+
+        // SAFETY: `sigevent` is a plain C struct for which all zeroes is
+        // valid. The timer is deleted when dropped.
+        let timer = unsafe {
+            let mut event: libc::sigevent = mem::zeroed();
+            event.sigev_notify = libc::SIGEV_THREAD_ID;
+            event.sigev_signo = SIGNAL;
+            event.sigev_notify_thread_id = libc::syscall(libc::SYS_gettid) as libc::pid_t;
+            let mut timer = ptr::null_mut();
+            if libc::timer_create(libc::CLOCK_MONOTONIC, &mut event, &mut timer) != 0 {
+                return None;
+            }
+            Timer(timer)
+        };
+
+        // A zero value would disarm the timer instead of firing right away.
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let spec = libc::itimerspec {
+            it_value: timespec(remaining.max(Duration::from_nanos(1))),
+            it_interval: timespec(TIMER_INTERVAL),
+        };
+        // SAFETY: the timer was created above, and `spec` outlives the call.
+        match unsafe { libc::timer_settime(timer.0, 0, &spec, ptr::null_mut()) } {
+            0 => Some(timer),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Drop for Timer {
+    fn drop(&mut self) {
+        // SAFETY: the timer is deleted exactly once.
+        unsafe { libc::timer_delete(self.0) };
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn timespec(duration: Duration) -> libc::timespec {
+    // SAFETY: `timespec` is a plain C struct for which all zeroes is valid.
+    let mut spec: libc::timespec = unsafe { mem::zeroed() };
+    spec.tv_sec = duration.as_secs().try_into().unwrap_or(libc::time_t::MAX);
+    spec.tv_nsec = duration.subsec_nanos() as _;
+    spec
+}
+
+/// Installs a handler which does nothing, unless the application already
+/// handles or explicitly ignores the signal. Without `SA_RESTART`, the
+/// signal then makes blocking system calls fail with `EINTR`.
+///
+/// Returns whether the signal interrupts system calls.
+fn interrupts_syscalls() -> bool {
+    static INTERRUPTS: OnceLock<bool> = OnceLock::new();
+    *INTERRUPTS.get_or_init(install_handler)
+}
+
+fn install_handler() -> bool {
+    extern "C" fn handler(_: libc::c_int) {}
+
+    // SAFETY: `sigaction` is a plain C struct for which all zeroes is valid,
+    // and the handler does nothing, so it is async-signal-safe.
+    unsafe {
+        let mut old: libc::sigaction = mem::zeroed();
+        if libc::sigaction(SIGNAL, ptr::null(), &mut old) != 0 {
+            return false;
+        }
+        match old.sa_sigaction {
+            libc::SIG_DFL => {}
+            // The application chose to ignore it, so leave it ignored.
+            libc::SIG_IGN => return false,
+            _ => return old.sa_flags & libc::SA_RESTART == 0,
+        }
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as *const () as libc::sighandler_t;
+        libc::sigaction(SIGNAL, &action, ptr::null_mut()) == 0
+    }
+}
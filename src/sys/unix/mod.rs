@@ -1,13 +1,37 @@
+mod interrupt;
+mod process;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod query;
 mod read_guard;
+mod registry;
 mod rw_lock;
+mod state;
 mod write_guard;
 
+pub(crate) use interrupt::Thread;
+pub(crate) use process::{pid_namespace, process_liveness};
 pub use read_guard::RwLockReadGuard;
 pub use rw_lock::RwLock;
 pub use write_guard::RwLockWriteGuard;
 
 use rustix::{fd::AsFd, fs};
 
+use crate::range::{ByteRange, MAX_END};
+
+/// The region locked by whole-file locks: every byte of the file, including
+/// bytes appended later.
+pub(crate) const WHOLE: ByteRange = ByteRange {
+    start: 0,
+    end: MAX_END,
+};
+
+/// Returns the device and inode number of an open file, which identify it.
+#[allow(clippy::unnecessary_cast)] // The types differ between platforms.
+pub(crate) fn file_id<Fd: AsFd>(fd: Fd) -> std::io::Result<(u64, u64)> {
+    let stat = fs::fstat(fd)?;
+    Ok((stat.st_dev as u64, stat.st_ino as u64))
+}
+
 pub(crate) fn compatible_unix_lock<Fd: AsFd>(
     fd: Fd,
     operation: fs::FlockOperation,
@@ -18,3 +42,62 @@ pub(crate) fn compatible_unix_lock<Fd: AsFd>(
     #[cfg(target_os = "solaris")]
     return fs::fcntl_lock(fd, operation);
 }
+
+/// Apply an open file description lock to a range of the file.
+///
+/// See: https://man7.org/linux/man-pages/man2/fcntl.2.html
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn ofd_lock<Fd: AsFd>(
+    fd: Fd,
+    operation: fs::FlockOperation,
+    range: ByteRange,
+) -> rustix::io::Result<()> {
+    use rustix::fd::AsRawFd;
+    use rustix::fs::FlockOperation;
+
+    let (cmd, l_type) = match operation {
+        FlockOperation::LockShared => (libc::F_OFD_SETLKW, libc::F_RDLCK),
+        FlockOperation::LockExclusive => (libc::F_OFD_SETLKW, libc::F_WRLCK),
+        FlockOperation::Unlock => (libc::F_OFD_SETLKW, libc::F_UNLCK),
+        FlockOperation::NonBlockingLockShared => (libc::F_OFD_SETLK, libc::F_RDLCK),
+        FlockOperation::NonBlockingLockExclusive => (libc::F_OFD_SETLK, libc::F_WRLCK),
+        FlockOperation::NonBlockingUnlock => (libc::F_OFD_SETLK, libc::F_UNLCK),
+    };
+
+    // `fcntl` offsets are signed. Reject ranges it can't represent here, as
+    // the kernel's `EINVAL` would be mistaken for missing OFD support.
+    let (l_start, l_len) = flock_range(range).ok_or(rustix::io::Errno::OVERFLOW)?;
+
+    // SAFETY: `flock` is a plain C struct for which all zeroes is valid. OFD
+    // locks require `l_pid` to be zero.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = l_type as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    lock.l_start = l_start;
+    lock.l_len = l_len;
+
+    // SAFETY: the descriptor is borrowed for the duration of the call and
+    // `lock` outlives it.
+    match unsafe { libc::fcntl(fd.as_fd().as_raw_fd(), cmd, &lock) } {
+        -1 => Err(rustix::io::Errno::from_raw_os_error(
+            std::io::Error::last_os_error()
+                .raw_os_error()
+                .unwrap_or_default(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Convert a byte range to the `l_start` and `l_len` of a `struct flock`.
+///
+/// Ranges ending past the largest `off_t` get a length of zero, which locks
+/// everything from `l_start` onwards.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn flock_range(range: ByteRange) -> Option<(libc::off_t, libc::off_t)> {
+    let start = libc::off_t::try_from(range.start).ok()?;
+    let len = match libc::off_t::try_from(range.end) {
+        Ok(end) => end - start,
+        Err(_) => 0,
+    };
+    Some((start, len))
+}
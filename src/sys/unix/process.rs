@@ -0,0 +1,94 @@
+use std::io;
+use std::time::SystemTime;
+
+use crate::liveness::Liveness;
+
+/// Checks whether the process `pid`, which was already running at
+/// `started`, still is.
+pub(crate) fn process_liveness(pid: u32, started: Option<SystemTime>) -> Liveness {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(liveness) = proc_liveness(pid, started) {
+        return liveness;
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let _ = started;
+
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return Liveness::Unknown;
+    };
+    if pid <= 0 {
+        return Liveness::Unknown;
+    }
+    // SAFETY: signal 0 only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Liveness::Alive;
+    }
+    match io::Error::last_os_error().raw_os_error() {
+        Some(libc::ESRCH) => Liveness::Dead,
+        // The process exists, but belongs to another user.
+        Some(libc::EPERM) => Liveness::Alive,
+        _ => Liveness::Unknown,
+    }
+}
+
+/// Returns the inode of the PID namespace of the current process, which
+/// identifies it.
+pub(crate) fn pid_namespace() -> Option<u64> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    return rustix::fs::stat("/proc/self/ns/pid")
+        .ok()
+        .map(|stat| stat.st_ino);
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    return None;
+}
+
+/// Checks the process in `/proc`, or returns `None` if it can't tell from
+/// there.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn proc_liveness(pid: u32, started: Option<SystemTime>) -> Option<Liveness> {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // How much later than `started` the start time read from `/proc` may be
+    // for the same process: both are truncated to whole seconds, and the
+    // wall clock may have been adjusted since the system booted.
+    const TOLERANCE: Duration = Duration::from_secs(2);
+
+    // A missing entry doesn't mean the process is gone: with `hidepid=2`,
+    // `/proc` hides the processes of other users. Leave that to `kill(2)`.
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // The command name in parentheses may contain spaces, so the fields are
+    // counted from the closing parenthesis: the state is field 3, the start
+    // time in clock ticks since boot field 22.
+    let mut fields = stat[stat.rfind(')')? + 1..].split_whitespace();
+    if fields.next()? == "Z" {
+        // A zombie has exited and released its locks.
+        return Some(Liveness::Dead);
+    }
+    let Some(started) = started else {
+        return Some(Liveness::Alive);
+    };
+    let ticks: u64 = fields.nth(18)?.parse().ok()?;
+
+    let boot = std::fs::read_to_string("/proc/stat").ok()?;
+    let boot: u64 = boot
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    // SAFETY: always safe to call.
+    let hz = match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        hz if hz > 0 => hz as u64,
+        _ => return None,
+    };
+
+    let process_started = UNIX_EPOCH + Duration::from_secs(boot + ticks / hz);
+    if process_started > started + TOLERANCE {
+        Some(Liveness::Dead)
+    } else {
+        Some(Liveness::Alive)
+    }
+}
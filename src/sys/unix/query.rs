@@ -0,0 +1,128 @@
+use rustix::fd::{AsRawFd, BorrowedFd};
+use rustix::fs;
+use std::io;
+
+use super::{flock_range, WHOLE};
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::range::{ByteRange, MAX_END};
+
+/// Finds an OFD or `fcntl` lock which conflicts with locking `range` in
+/// `mode`, using `F_OFD_GETLK`.
+///
+/// Locks held through the same open file description never conflict, so
+/// these are left out.
+pub(crate) fn ofd_conflict(
+    fd: BorrowedFd<'_>,
+    mode: LockMode,
+    range: ByteRange,
+) -> io::Result<Option<LockHolder>> {
+    let (l_start, l_len) = flock_range(range)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "byte range too large"))?;
+
+    // SAFETY: `flock` is a plain C struct for which all zeroes is valid. OFD
+    // locks require `l_pid` to be zero.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = match mode {
+        LockMode::Shared => libc::F_RDLCK,
+        LockMode::Exclusive => libc::F_WRLCK,
+    } as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    lock.l_start = l_start;
+    lock.l_len = l_len;
+
+    // SAFETY: the descriptor is borrowed for the duration of the call and
+    // `lock` outlives it.
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_OFD_GETLK, &mut lock) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mode = match lock.l_type as libc::c_int {
+        libc::F_RDLCK => LockMode::Shared,
+        libc::F_WRLCK => LockMode::Exclusive,
+        _ => return Ok(None),
+    };
+    let start = lock.l_start as u64;
+    let end = match lock.l_len {
+        0 => MAX_END,
+        len => start.saturating_add(len as u64).min(MAX_END),
+    };
+    // OFD locks report a PID of -1, classic `fcntl` locks their owner.
+    let pid = u32::try_from(lock.l_pid).ok().filter(|&pid| pid != 0);
+    Ok(Some(LockHolder::new(mode, ByteRange { start, end }, pid)))
+}
+
+/// Finds a `flock` lock which conflicts with locking the file in `mode`, by
+/// looking the file up in `/proc/locks`.
+///
+/// This lists the locks of every open file description, including the one
+/// of `fd`. Callers must rule out conflicts with its own locks first.
+///
+/// `/proc/locks` names files by the device of their file system, which
+/// `fstat` doesn't report on btrfs subvolumes, so the device of the mount is
+/// tried as well. Files which match neither, as may happen on overlayfs,
+/// aren't found.
+pub(crate) fn flock_conflict(fd: BorrowedFd<'_>, mode: LockMode) -> io::Result<Option<LockHolder>> {
+    let stat = fs::fstat(fd)?;
+    let mut devices = vec![(fs::major(stat.st_dev), fs::minor(stat.st_dev))];
+    if let Some(device) = mount_device(fd) {
+        devices.push(device);
+    }
+
+    // Lines look like `1: FLOCK  ADVISORY  WRITE 1234 fe:00:5678 0 EOF`.
+    // Waiters are listed below the lock they wait for, with a `->` in place
+    // of the lock type, so they never match.
+    let locks = std::fs::read_to_string("/proc/locks")?;
+    for line in locks.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [_, "FLOCK", _, kind, pid, id, ..] = fields[..] else {
+            continue;
+        };
+        let Some((device, ino)) = parse_file_id(id) else {
+            continue;
+        };
+        if ino != stat.st_ino || !devices.contains(&device) {
+            continue;
+        }
+        let held = match kind {
+            "WRITE" => LockMode::Exclusive,
+            "READ" if mode == LockMode::Exclusive => LockMode::Shared,
+            _ => continue,
+        };
+        let pid = pid.parse().ok().filter(|&pid| pid != 0);
+        return Ok(Some(LockHolder::new(held, WHOLE, pid)));
+    }
+    Ok(None)
+}
+
+/// Parses a file as `/proc/locks` shows it: `major:minor:inode`, with the
+/// device numbers in hexadecimal.
+fn parse_file_id(id: &str) -> Option<((u32, u32), u64)> {
+    let mut parts = id.split(':');
+    let major = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let minor = u32::from_str_radix(parts.next()?, 16).ok()?;
+    let ino = parts.next()?.parse().ok()?;
+    Some(((major, minor), ino))
+}
+
+/// Returns the device of the mount `fd` was opened on, from
+/// `/proc/self/mountinfo`.
+fn mount_device(fd: BorrowedFd<'_>) -> Option<(u32, u32)> {
+    let fdinfo = std::fs::read_to_string(format!("/proc/self/fdinfo/{}", fd.as_raw_fd())).ok()?;
+    let mount = fdinfo
+        .lines()
+        .find_map(|line| line.strip_prefix("mnt_id:"))?
+        .trim();
+
+    // Lines start with `36 35 98:0 /mnt1 ...`: the mount ID, the ID of its
+    // parent and the device, in decimal.
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != mount {
+            return None;
+        }
+        let (major, minor) = fields.nth(1)?.split_once(':')?;
+        Some((major.parse().ok()?, minor.parse().ok()?))
+    })
+}
@@ -1,17 +1,26 @@
 use rustix::fd::AsFd;
-use rustix::fs::FlockOperation;
-use std::ops;
+use std::{io, ops};
 
-use super::{compatible_unix_lock, RwLock};
+use super::RwLock;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::unlock;
 
 #[derive(Debug)]
 pub struct RwLockReadGuard<'lock, T: AsFd> {
     lock: &'lock RwLock<T>,
+    range: ByteRange,
 }
 
 impl<'lock, T: AsFd> RwLockReadGuard<'lock, T> {
-    pub(crate) fn new(lock: &'lock RwLock<T>) -> Self {
-        Self { lock }
+    pub(crate) fn new(lock: &'lock RwLock<T>, range: ByteRange) -> Self {
+        Self { lock, range }
+    }
+
+    /// Unlocks the range without consuming the guard. The caller must forget
+    /// the guard if this succeeds.
+    pub(crate) fn unlock(&self) -> io::Result<()> {
+        self.lock.unlock(LockMode::Shared, self.range)
     }
 }
 
@@ -27,6 +36,6 @@ impl<T: AsFd> ops::Deref for RwLockReadGuard<'_, T> {
 impl<T: AsFd> Drop for RwLockReadGuard<'_, T> {
     #[inline]
     fn drop(&mut self) {
-        let _ = compatible_unix_lock(self.lock.inner.as_fd(), FlockOperation::Unlock).ok();
+        unlock::report(self.lock.release(LockMode::Shared, self.range));
     }
 }
@@ -0,0 +1,131 @@
+use std::io::{self, ErrorKind};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::same_process_policy::{same_process_policy, SameProcessPolicy};
+
+/// The device and inode number of a file.
+pub(crate) type FileId = (u64, u64);
+
+/// How often a waiting acquisition checks whether it was cancelled or timed
+/// out.
+const POLL: Duration = Duration::from_millis(10);
+
+/// The locks every `RwLock` of this process holds, while a
+/// [`SameProcessPolicy`] other than `Ignore` is set.
+static HOLDS: Mutex<Vec<Hold>> = Mutex::new(Vec::new());
+
+/// Notified whenever a hold is removed from `HOLDS`.
+static RELEASED: Condvar = Condvar::new();
+
+#[derive(Debug)]
+struct Hold {
+    file: FileId,
+    /// Identifies the `RwLock` which holds the lock.
+    owner: usize,
+    mode: LockMode,
+    range: ByteRange,
+}
+
+impl Hold {
+    fn conflicts(&self, file: FileId, owner: usize, mode: LockMode, range: &ByteRange) -> bool {
+        self.file == file
+            && self.owner != owner
+            && (self.mode == LockMode::Exclusive || mode == LockMode::Exclusive)
+            && self.range.overlaps(range)
+    }
+}
+
+/// Returns whether locks are tracked.
+pub(crate) fn enabled() -> bool {
+    same_process_policy() != SameProcessPolicy::Ignore
+}
+
+/// Records that `owner` locks `range` of `file` in `mode`.
+///
+/// If another lock of this process holds a conflicting lock, non-blocking
+/// acquisitions fail with `WouldBlock`. Blocking ones fail with `Deadlock`
+/// or wait, depending on the policy. While waiting, `check` is called
+/// regularly, and its error is returned.
+pub(crate) fn acquire(
+    file: FileId,
+    owner: usize,
+    mode: LockMode,
+    range: ByteRange,
+    blocking: bool,
+    check: impl Fn() -> io::Result<()>,
+) -> io::Result<()> {
+    let mut holds = holds();
+    while holds
+        .iter()
+        .any(|hold| hold.conflicts(file, owner, mode, &range))
+    {
+        if !blocking {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if same_process_policy() != SameProcessPolicy::Wait {
+            return Err(io::Error::new(
+                ErrorKind::Deadlock,
+                "the file is already locked by this process through another handle",
+            ));
+        }
+        check()?;
+        holds = RELEASED
+            .wait_timeout(holds, POLL)
+            .map(|(holds, _)| holds)
+            .unwrap_or_else(|err| err.into_inner().0);
+    }
+    holds.push(Hold {
+        file,
+        owner,
+        mode,
+        range,
+    });
+    Ok(())
+}
+
+/// Removes a hold recorded with [`acquire`], if there is one.
+pub(crate) fn release(file: FileId, owner: usize, mode: LockMode, range: ByteRange) {
+    let mut holds = holds();
+    let index = holds.iter().position(|hold| {
+        hold.file == file && hold.owner == owner && hold.mode == mode && hold.range == range
+    });
+    if let Some(index) = index {
+        holds.swap_remove(index);
+        RELEASED.notify_all();
+    }
+}
+
+/// Removes every hold of `owner` on `file`, such as the ones of guards which
+/// were forgotten instead of dropped.
+pub(crate) fn release_all(file: FileId, owner: usize) {
+    let mut holds = holds();
+    let len = holds.len();
+    holds.retain(|hold| hold.file != file || hold.owner != owner);
+    if holds.len() != len {
+        RELEASED.notify_all();
+    }
+}
+
+/// Turns an exclusive hold into a shared one.
+pub(crate) fn downgrade(file: FileId, owner: usize, range: ByteRange) {
+    let mut holds = holds();
+    let hold = holds.iter_mut().find(|hold| {
+        hold.file == file
+            && hold.owner == owner
+            && hold.mode == LockMode::Exclusive
+            && hold.range == range
+    });
+    if let Some(hold) = hold {
+        hold.mode = LockMode::Shared;
+        RELEASED.notify_all();
+    }
+}
+
+/// The holds are only changed in one step, so a poisoned mutex still holds
+/// accurate ones.
+fn holds() -> MutexGuard<'static, Vec<Hold>> {
+    HOLDS.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -1,51 +1,63 @@
-use rustix::fd::AsFd;
-use rustix::fs::FlockOperation;
+use rustix::fd::{AsFd, OwnedFd};
 use std::io::{self, Error, ErrorKind};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use super::{compatible_unix_lock, RwLockReadGuard, RwLockWriteGuard};
+use super::state::{Interrupt, State};
+use super::{RwLockReadGuard, RwLockWriteGuard, WHOLE};
+use crate::cancel_token::{self, CancelToken};
+use crate::interrupt_policy::InterruptPolicy;
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::Backend;
 
 #[derive(Debug)]
 pub struct RwLock<T: AsFd> {
     pub(crate) inner: T,
+    state: Arc<State>,
 }
 
 impl<T: AsFd> RwLock<T> {
     #[inline]
     pub fn new(inner: T) -> Self {
-        RwLock { inner }
+        Self::with_backend(inner, Backend::Native)
     }
 
     #[inline]
-    pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
-        compatible_unix_lock(self.inner.as_fd(), FlockOperation::LockExclusive)?;
-        Ok(RwLockWriteGuard::new(self))
+    pub fn with_backend(inner: T, backend: Backend) -> Self {
+        RwLock {
+            inner,
+            state: Arc::new(State::new(backend)),
+        }
     }
 
     #[inline]
-    pub fn try_write(&mut self) -> Result<RwLockWriteGuard<'_, T>, Error> {
-        compatible_unix_lock(self.inner.as_fd(), FlockOperation::NonBlockingLockExclusive)
-            .map_err(|err| match err.kind() {
-                ErrorKind::AlreadyExists => ErrorKind::WouldBlock.into(),
-                _ => Error::from(err),
-            })?;
-        Ok(RwLockWriteGuard::new(self))
+    pub fn backend(&self) -> Backend {
+        self.state.backend()
+    }
+
+    #[inline]
+    pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.write_range(WHOLE)
     }
 
     #[inline]
     pub fn read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
-        compatible_unix_lock(self.inner.as_fd(), FlockOperation::LockShared)?;
-        Ok(RwLockReadGuard::new(self))
+        self.read_range(WHOLE)
     }
 
     #[inline]
-    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, Error> {
-        compatible_unix_lock(self.inner.as_fd(), FlockOperation::NonBlockingLockShared).map_err(
-            |err| match err.kind() {
-                ErrorKind::AlreadyExists => ErrorKind::WouldBlock.into(),
-                _ => Error::from(err),
-            },
-        )?;
-        Ok(RwLockReadGuard::new(self))
+    pub fn write_range(&mut self, range: ByteRange) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock(LockMode::Exclusive, range, true)?;
+        Ok(RwLockWriteGuard::new(self, range))
+    }
+
+    #[inline]
+    pub fn read_range(&self, range: ByteRange) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock(LockMode::Shared, range, true)?;
+        Ok(RwLockReadGuard::new(self, range))
     }
 
     #[inline]
@@ -55,4 +67,174 @@ impl<T: AsFd> RwLock<T> {
     {
         self.inner
     }
+
+    /// Locks a range without creating a guard for it.
+    ///
+    /// Shared locks are registered as readers, and only lock the range if no
+    /// other reader already covers it.
+    #[inline]
+    pub(crate) fn lock(&self, mode: LockMode, range: ByteRange, blocking: bool) -> io::Result<()> {
+        let interrupt = self.state.interrupt();
+        self.state
+            .lock(self.inner.as_fd(), mode, range, blocking, interrupt)
+    }
+
+    /// Locks a range, blocking, with the given policy instead of this lock's
+    /// own one.
+    #[inline]
+    pub(crate) fn lock_with(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        policy: InterruptPolicy,
+    ) -> io::Result<()> {
+        let interrupt = match policy {
+            InterruptPolicy::Return => Interrupt::Return,
+            InterruptPolicy::Retry => Interrupt::Retry,
+        };
+        self.state
+            .lock(self.inner.as_fd(), mode, range, true, interrupt)
+    }
+
+    /// Locks a range, blocking until it is acquired or `token` is
+    /// cancelled.
+    ///
+    /// Where the signal can't interrupt the wait, this polls the lock
+    /// instead, backing off exponentially up to a few tens of milliseconds.
+    pub(crate) fn lock_cancellable(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        token: &CancelToken,
+    ) -> io::Result<()> {
+        if let Some(_waiter) = token.register() {
+            let interrupt = Interrupt::Cancel(token);
+            return self
+                .state
+                .lock(self.inner.as_fd(), mode, range, true, interrupt);
+        }
+
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            if token.is_cancelled() {
+                return Err(cancel_token::cancelled());
+            }
+            match self.lock(mode, range, false) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_millis(50));
+        }
+    }
+
+    /// Locks a range, blocking in the kernel until it is acquired or the
+    /// deadline passes.
+    ///
+    /// Returns `None` where the thread can't be woken up by a timer, in
+    /// which case the caller has to poll.
+    pub(crate) fn lock_until(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        deadline: Instant,
+    ) -> Option<io::Result<()>> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let _timer = super::interrupt::Timer::start(deadline)?;
+            let interrupt = Interrupt::Deadline(deadline);
+            Some(
+                self.state
+                    .lock(self.inner.as_fd(), mode, range, true, interrupt),
+            )
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = (mode, range, deadline);
+            None
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_interrupt_policy(&self, policy: InterruptPolicy) {
+        self.state
+            .set_retry_interrupted(policy == InterruptPolicy::Retry);
+    }
+
+    /// Unlocks a range previously locked with [`RwLock::lock`].
+    #[inline]
+    pub(crate) fn unlock(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        self.state.unlock(self.inner.as_fd(), mode, range)
+    }
+
+    /// Unlocks a range for good. If unlocking fails the range is forgotten
+    /// anyway, so other threads of this process don't wait for it forever.
+    #[inline]
+    pub(crate) fn release(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        let result = self.unlock(mode, range);
+        if result.is_err() {
+            self.state.forget(mode, range);
+        }
+        result
+    }
+
+    /// Converts the exclusive lock on a range into a shared one.
+    #[inline]
+    pub(crate) fn downgrade(&self, range: ByteRange) -> io::Result<()> {
+        self.state.downgrade(self.inner.as_fd(), range)
+    }
+
+    /// Converts a reader's shared lock on a range into an exclusive one. If
+    /// the conversion fails the range is left unlocked.
+    #[inline]
+    pub(crate) fn upgrade(&self, range: ByteRange) -> io::Result<()> {
+        self.state.upgrade(self.inner.as_fd(), range)
+    }
+
+    /// Finds a lock which keeps `range` from being locked in `mode`, held
+    /// either through this lock or through another open file description.
+    pub(crate) fn conflicting_lock(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+    ) -> io::Result<Option<LockHolder>> {
+        if let Some(holder) = self.state.conflicting(mode, range) {
+            return Ok(Some(holder));
+        }
+        match self.backend() {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Backend::Ofd => super::query::ofd_conflict(self.inner.as_fd(), mode, range),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            Backend::Native if range == WHOLE => {
+                super::query::flock_conflict(self.inner.as_fd(), mode)
+            }
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                "querying lock holders is not supported on this platform",
+            )),
+        }
+    }
+
+    /// Creates a second handle to this lock, backed by a duplicate of its
+    /// file descriptor.
+    ///
+    /// Both handles share the same open file description and the same
+    /// bookkeeping, so locks taken through one can be released through the
+    /// other.
+    #[cfg_attr(not(any(feature = "async", feature = "tokio")), allow(dead_code))]
+    pub(crate) fn detach(&self) -> io::Result<RwLock<OwnedFd>> {
+        // POSIX record locks are released as soon as any descriptor of the
+        // file is closed, which would drop the locks of the original handle.
+        if cfg!(target_os = "solaris") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "detached locks are not supported on this platform",
+            ));
+        }
+        Ok(RwLock {
+            inner: self.inner.as_fd().try_clone_to_owned()?,
+            state: Arc::clone(&self.state),
+        })
+    }
 }
@@ -0,0 +1,506 @@
+use rustix::fd::BorrowedFd;
+use rustix::fs::FlockOperation;
+use std::io::{self, Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::registry::{self, FileId};
+use super::{compatible_unix_lock, file_id, WHOLE};
+use crate::cancel_token::{self, CancelToken};
+use crate::error;
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::Backend;
+
+/// The locking state shared by every descriptor of one `RwLock`.
+///
+/// `flock` and OFD locks belong to the open file description rather than to
+/// the guard or the thread. Locking a range this description already holds
+/// succeeds immediately, and unlocking it releases it for everyone. This
+/// keeps track of which ranges are held, so readers only unlock what no other
+/// reader still covers and exclusive locks wait for in-process conflicts
+/// instead of silently merging with them.
+#[derive(Debug)]
+pub(crate) struct State {
+    ranges: Mutex<Ranges>,
+    /// Notified whenever a range is removed from `ranges`.
+    released: Condvar,
+    /// Whether this lock uses OFD locks. Cleared when the kernel turns out
+    /// not to support them.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    ofd: AtomicBool,
+    /// Whether blocking acquisitions interrupted by a signal are retried,
+    /// unless the caller says otherwise.
+    retry_interrupted: AtomicBool,
+    /// The file as known to the process-wide registry, once this lock was
+    /// registered there.
+    file: OnceLock<Option<FileId>>,
+}
+
+/// How often threads waiting for another thread of this process check
+/// whether they were cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// What a blocking acquisition does when a signal interrupts it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Interrupt<'a> {
+    /// Return the `EINTR` error.
+    Return,
+    /// Try again.
+    Retry,
+    /// Fail if the token was cancelled, and try again otherwise.
+    Cancel(&'a CancelToken),
+    /// Fail if the deadline passed, and try again otherwise.
+    Deadline(Instant),
+}
+
+impl Interrupt<'_> {
+    /// Fails if the acquisition was cancelled.
+    fn check(&self) -> io::Result<()> {
+        match self {
+            Interrupt::Cancel(token) if token.is_cancelled() => Err(cancel_token::cancelled()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a wait should stop, because the acquisition was cancelled or
+    /// the deadline passed.
+    fn check_wait(&self) -> io::Result<()> {
+        self.check()?;
+        match self {
+            Interrupt::Deadline(deadline) if Instant::now() >= *deadline => Err(error::timed_out()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The ranges held, or being acquired, through one open file description.
+#[derive(Debug, Default)]
+struct Ranges {
+    /// One entry per live reader.
+    shared: Vec<ByteRange>,
+    /// Shared locks which are still being acquired.
+    pending: Vec<ByteRange>,
+    exclusive: Vec<ByteRange>,
+}
+
+impl State {
+    pub(crate) fn new(backend: Backend) -> Self {
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        let _ = backend;
+        State {
+            ranges: Mutex::new(Ranges::default()),
+            released: Condvar::new(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            ofd: (backend == Backend::Ofd).into(),
+            retry_interrupted: AtomicBool::new(false),
+            file: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn backend(&self) -> Backend {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.ofd.load(Ordering::Relaxed) {
+            return Backend::Ofd;
+        }
+        Backend::Native
+    }
+
+    /// What acquisitions do when interrupted, unless the caller says
+    /// otherwise.
+    pub(crate) fn interrupt(&self) -> Interrupt<'static> {
+        match self.retry_interrupted.load(Ordering::Relaxed) {
+            true => Interrupt::Retry,
+            false => Interrupt::Return,
+        }
+    }
+
+    pub(crate) fn set_retry_interrupted(&self, retry: bool) {
+        self.retry_interrupted.store(retry, Ordering::Relaxed);
+    }
+
+    /// Locks a range of the file, handling signals as `interrupt` says.
+    ///
+    /// Shared locks are registered as readers, and only lock the range if no
+    /// other reader already covers it.
+    pub(crate) fn lock(
+        &self,
+        fd: BorrowedFd<'_>,
+        mode: LockMode,
+        range: ByteRange,
+        blocking: bool,
+        interrupt: Interrupt<'_>,
+    ) -> io::Result<()> {
+        let mut ranges = self.ranges();
+        match mode {
+            LockMode::Shared => {
+                while ranges.exclusive.iter().any(|held| held.overlaps(&range)) {
+                    ranges = self.wait(ranges, blocking, interrupt)?;
+                }
+                // A range another reader already holds is locked through the
+                // open file description, and only needs to be registered.
+                let covered = ranges.shared.iter().any(|held| held.contains(&range));
+                // Mark the range as pending, so other readers don't unlock it
+                // from under us while the lock is being acquired. Registering
+                // may wait for other threads, so don't hold the ranges then.
+                ranges.pending.push(range);
+                drop(ranges);
+
+                let operation = match blocking {
+                    true => FlockOperation::LockShared,
+                    false => FlockOperation::NonBlockingLockShared,
+                };
+                let result = self.register(fd, mode, range, blocking, interrupt);
+                let result = result.and_then(|()| {
+                    if covered {
+                        return Ok(());
+                    }
+                    let result = self.apply_retrying(fd, operation, range, interrupt);
+                    if result.is_err() {
+                        self.unregister(mode, range);
+                    }
+                    result
+                });
+
+                let mut ranges = self.ranges();
+                if let Some(index) = ranges.pending.iter().position(|held| *held == range) {
+                    ranges.pending.swap_remove(index);
+                }
+                match result {
+                    Ok(()) => ranges.shared.push(range),
+                    // A reader which left in the meantime may have kept parts
+                    // of the range locked on our behalf.
+                    Err(_) => drop(self.release(fd, &ranges, range)),
+                }
+                self.released.notify_all();
+                result
+            }
+            LockMode::Exclusive => {
+                while ranges.overlaps(&range) {
+                    ranges = self.wait(ranges, blocking, interrupt)?;
+                }
+                // Reserve the range, so it can be locked without blocking
+                // other threads of this process in the meantime.
+                ranges.exclusive.push(range);
+                drop(ranges);
+
+                let operation = match blocking {
+                    true => FlockOperation::LockExclusive,
+                    false => FlockOperation::NonBlockingLockExclusive,
+                };
+                let result = self.register(fd, mode, range, blocking, interrupt);
+                let result =
+                    result.and_then(|()| self.apply_retrying(fd, operation, range, interrupt));
+                if result.is_err() {
+                    self.forget(LockMode::Exclusive, range);
+                }
+                result
+            }
+        }
+    }
+
+    /// Unlocks a range previously locked with [`State::lock`].
+    ///
+    /// Shared locks only unlock the parts of their range which no other
+    /// reader still covers. If unlocking fails the range is still considered
+    /// held, so it can be retried.
+    pub(crate) fn unlock(
+        &self,
+        fd: BorrowedFd<'_>,
+        mode: LockMode,
+        range: ByteRange,
+    ) -> io::Result<()> {
+        if mode == LockMode::Exclusive {
+            self.apply(fd, FlockOperation::Unlock, range)?;
+            self.forget(LockMode::Exclusive, range);
+            return Ok(());
+        }
+
+        let mut ranges = self.ranges();
+        ranges.remove(LockMode::Shared, range);
+        if let Err(err) = self.release(fd, &ranges, range) {
+            ranges.shared.push(range);
+            return Err(err);
+        }
+        self.unregister(LockMode::Shared, range);
+        self.released.notify_all();
+        Ok(())
+    }
+
+    /// Forgets a held range without unlocking it, and wakes up threads
+    /// waiting for it.
+    pub(crate) fn forget(&self, mode: LockMode, range: ByteRange) {
+        self.ranges().remove(mode, range);
+        self.unregister(mode, range);
+        self.released.notify_all();
+    }
+
+    /// Finds a lock taken through this open file description which conflicts
+    /// with locking `range` in `mode`.
+    ///
+    /// The kernel never reports these, as a description doesn't conflict
+    /// with itself.
+    pub(crate) fn conflicting(&self, mode: LockMode, range: ByteRange) -> Option<LockHolder> {
+        let ranges = self.ranges();
+        let pid = Some(std::process::id());
+        if let Some(held) = ranges.exclusive.iter().find(|held| held.overlaps(&range)) {
+            return Some(LockHolder::new(LockMode::Exclusive, *held, pid));
+        }
+        if mode == LockMode::Shared {
+            return None;
+        }
+        let mut readers = ranges.shared.iter().chain(&ranges.pending);
+        let held = readers.find(|held| held.overlaps(&range))?;
+        Some(LockHolder::new(LockMode::Shared, *held, pid))
+    }
+
+    /// Converts the exclusive lock on a range into a shared one, registering
+    /// it as a reader.
+    ///
+    /// OFD and `fcntl` locks convert atomically. `flock` first drops the
+    /// exclusive lock, so this may block if another process gets in between.
+    pub(crate) fn downgrade(&self, fd: BorrowedFd<'_>, range: ByteRange) -> io::Result<()> {
+        // The range stays reserved until the conversion is done.
+        let interrupt = self.interrupt();
+        self.apply_retrying(fd, FlockOperation::LockShared, range, interrupt)?;
+        let mut ranges = self.ranges();
+        ranges.remove(LockMode::Exclusive, range);
+        ranges.shared.push(range);
+        if let Some(file) = self.registered_file() {
+            registry::downgrade(file, self.owner(), range);
+        }
+        self.released.notify_all();
+        Ok(())
+    }
+
+    /// Converts a reader's shared lock on a range into an exclusive one. If
+    /// the conversion fails the range is left unlocked.
+    ///
+    /// Waits for the other readers of this process to leave first. OFD and
+    /// `fcntl` locks then keep the shared lock while waiting for readers in
+    /// other processes. `flock` drops it first, letting another writer in.
+    pub(crate) fn upgrade(&self, fd: BorrowedFd<'_>, range: ByteRange) -> io::Result<()> {
+        let mut ranges = self.ranges();
+        // Reserve the range to keep new readers out while the others leave.
+        ranges.exclusive.push(range);
+        while ranges.readers(&range) > 1 {
+            ranges = self.wait(ranges, true, Interrupt::Return)?;
+        }
+        ranges.remove(LockMode::Shared, range);
+        drop(ranges);
+        self.unregister(LockMode::Shared, range);
+
+        let interrupt = self.interrupt();
+        let result = self.register(fd, LockMode::Exclusive, range, true, interrupt);
+        let result = result.and_then(|()| {
+            self.apply_retrying(fd, FlockOperation::LockExclusive, range, interrupt)
+        });
+        if result.is_err() {
+            let _ = self.apply(fd, FlockOperation::Unlock, range);
+            self.forget(LockMode::Exclusive, range);
+        }
+        result
+    }
+
+    /// Records a lock in the process-wide registry, if enabled, failing or
+    /// waiting while another lock of this process holds a conflicting one.
+    fn register(
+        &self,
+        fd: BorrowedFd<'_>,
+        mode: LockMode,
+        range: ByteRange,
+        blocking: bool,
+        interrupt: Interrupt<'_>,
+    ) -> io::Result<()> {
+        if !registry::enabled() {
+            return Ok(());
+        }
+        // Files which can't be identified are left out.
+        let Some(file) = *self.file.get_or_init(|| file_id(fd).ok()) else {
+            return Ok(());
+        };
+        registry::acquire(file, self.owner(), mode, range, blocking, || {
+            interrupt.check_wait()
+        })
+    }
+
+    /// Removes a lock from the process-wide registry, if it was recorded.
+    fn unregister(&self, mode: LockMode, range: ByteRange) {
+        if let Some(file) = self.registered_file() {
+            registry::release(file, self.owner(), mode, range);
+        }
+    }
+
+    fn registered_file(&self) -> Option<FileId> {
+        self.file.get().copied().flatten()
+    }
+
+    /// Identifies this lock in the process-wide registry. Every descriptor
+    /// of the lock shares this state, so they count as one.
+    fn owner(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Applies a lock operation to a range using this lock's backend.
+    fn apply(
+        &self,
+        fd: BorrowedFd<'_>,
+        operation: FlockOperation,
+        range: ByteRange,
+    ) -> io::Result<()> {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if self.backend() == Backend::Ofd {
+            match super::ofd_lock(fd, operation, range) {
+                Err(rustix::io::Errno::INVAL) => self.ofd.store(false, Ordering::Relaxed),
+                Err(rustix::io::Errno::AGAIN | rustix::io::Errno::ACCESS) => {
+                    return Err(ErrorKind::WouldBlock.into())
+                }
+                Err(rustix::io::Errno::OVERFLOW) => {
+                    return Err(Error::new(ErrorKind::InvalidInput, "byte range too large"))
+                }
+                result => return result.map_err(Error::from),
+            }
+        }
+
+        if range != WHOLE {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "byte-range locks require the OFD backend",
+            ));
+        }
+        compatible_unix_lock(fd, operation).map_err(|err| match err {
+            // `fcntl(2)` may report contention as `EACCES` instead of
+            // `EAGAIN`.
+            #[cfg(target_os = "solaris")]
+            rustix::io::Errno::ACCESS => ErrorKind::WouldBlock.into(),
+            _ if err.kind() == ErrorKind::AlreadyExists => ErrorKind::WouldBlock.into(),
+            _ => Error::from(err),
+        })
+    }
+
+    /// Applies a lock operation, handling signals which interrupt it as
+    /// `interrupt` says.
+    fn apply_retrying(
+        &self,
+        fd: BorrowedFd<'_>,
+        operation: FlockOperation,
+        range: ByteRange,
+        interrupt: Interrupt<'_>,
+    ) -> io::Result<()> {
+        loop {
+            interrupt.check()?;
+            match self.apply(fd, operation, range) {
+                Err(err) if err.kind() == ErrorKind::Interrupted => match interrupt {
+                    Interrupt::Return => return Err(err),
+                    Interrupt::Deadline(deadline) if Instant::now() >= deadline => {
+                        return Err(error::timed_out())
+                    }
+                    Interrupt::Retry | Interrupt::Cancel(_) | Interrupt::Deadline(_) => {}
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// Unlocks the parts of a range which no reader covers anymore.
+    fn release(&self, fd: BorrowedFd<'_>, ranges: &Ranges, range: ByteRange) -> io::Result<()> {
+        let covered: Vec<ByteRange> = ranges
+            .shared
+            .iter()
+            .chain(&ranges.pending)
+            .copied()
+            .collect();
+        let mut result = Ok(());
+        for part in range.subtract(&covered) {
+            let unlocked = self.apply(fd, FlockOperation::Unlock, part);
+            result = result.and(unlocked);
+        }
+        result
+    }
+
+    /// Waits for another thread to release a range, or fails right away if
+    /// the caller doesn't want to block.
+    ///
+    /// Signals don't wake up the wait, so cancellable waits poll the token
+    /// and timed waits stop at the deadline.
+    fn wait<'a>(
+        &self,
+        ranges: MutexGuard<'a, Ranges>,
+        blocking: bool,
+        interrupt: Interrupt<'_>,
+    ) -> io::Result<MutexGuard<'a, Ranges>> {
+        if !blocking {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        interrupt.check()?;
+        let timeout = match interrupt {
+            Interrupt::Cancel(_) => Some(CANCEL_POLL),
+            Interrupt::Deadline(deadline) => {
+                match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(error::timed_out()),
+                }
+            }
+            Interrupt::Return | Interrupt::Retry => None,
+        };
+        let ranges = match timeout {
+            Some(timeout) => self
+                .released
+                .wait_timeout(ranges, timeout)
+                .map(|(ranges, _)| ranges)
+                .unwrap_or_else(|err| err.into_inner().0),
+            _ => self
+                .released
+                .wait(ranges)
+                .unwrap_or_else(PoisonError::into_inner),
+        };
+        Ok(ranges)
+    }
+
+    /// The ranges only change after the matching syscall succeeded, so a
+    /// poisoned mutex still holds accurate ranges.
+    fn ranges(&self) -> MutexGuard<'_, Ranges> {
+        self.ranges.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Forget the holds of guards which were leaked instead of dropped, so they
+/// don't outlive the lock, or get inherited by a `State` allocated at the
+/// same address later.
+impl Drop for State {
+    fn drop(&mut self) {
+        if let Some(file) = self.registered_file() {
+            registry::release_all(file, self.owner());
+        }
+    }
+}
+
+impl Ranges {
+    /// Whether any held or pending range overlaps `range`.
+    fn overlaps(&self, range: &ByteRange) -> bool {
+        let mut held = self
+            .shared
+            .iter()
+            .chain(&self.pending)
+            .chain(&self.exclusive);
+        held.any(|held| held.overlaps(range))
+    }
+
+    /// The number of held or pending readers overlapping `range`.
+    fn readers(&self, range: &ByteRange) -> usize {
+        let readers = self.shared.iter().chain(&self.pending);
+        readers.filter(|held| held.overlaps(range)).count()
+    }
+
+    fn remove(&mut self, mode: LockMode, range: ByteRange) {
+        let held = match mode {
+            LockMode::Shared => &mut self.shared,
+            LockMode::Exclusive => &mut self.exclusive,
+        };
+        if let Some(index) = held.iter().position(|held| *held == range) {
+            held.swap_remove(index);
+        }
+    }
+}
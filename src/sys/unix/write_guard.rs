@@ -1,17 +1,43 @@
 use rustix::fd::AsFd;
-use rustix::fs::FlockOperation;
-use std::ops;
+use std::mem::ManuallyDrop;
+use std::{io, ops, ptr};
 
-use super::{compatible_unix_lock, RwLock};
+use super::{RwLock, RwLockReadGuard};
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::unlock;
 
 #[derive(Debug)]
 pub struct RwLockWriteGuard<'lock, T: AsFd> {
     lock: &'lock mut RwLock<T>,
+    range: ByteRange,
 }
 
 impl<'lock, T: AsFd> RwLockWriteGuard<'lock, T> {
-    pub(crate) fn new(lock: &'lock mut RwLock<T>) -> Self {
-        Self { lock }
+    pub(crate) fn new(lock: &'lock mut RwLock<T>, range: ByteRange) -> Self {
+        Self { lock, range }
+    }
+
+    /// Unlocks the range without consuming the guard. The caller must forget
+    /// the guard if this succeeds.
+    pub(crate) fn unlock(&self) -> io::Result<()> {
+        self.lock.unlock(LockMode::Exclusive, self.range)
+    }
+
+    /// Converts this guard into a read guard for the same range. If the
+    /// conversion fails the lock is released.
+    pub(crate) fn downgrade(self) -> io::Result<RwLockReadGuard<'lock, T>> {
+        let guard = ManuallyDrop::new(self);
+        // SAFETY: `guard` is never used or dropped again, so the reference is
+        // moved out of it exactly once.
+        let lock: &'lock mut RwLock<T> = unsafe { ptr::read(&guard.lock) };
+        match lock.downgrade(guard.range) {
+            Ok(()) => Ok(RwLockReadGuard::new(lock, guard.range)),
+            Err(err) => {
+                unlock::report(lock.release(LockMode::Exclusive, guard.range));
+                Err(err)
+            }
+        }
     }
 }
 
@@ -34,6 +60,6 @@ impl<T: AsFd> ops::DerefMut for RwLockWriteGuard<'_, T> {
 impl<T: AsFd> Drop for RwLockWriteGuard<'_, T> {
     #[inline]
     fn drop(&mut self) {
-        let _ = compatible_unix_lock(self.lock.inner.as_fd(), FlockOperation::Unlock).ok();
+        unlock::report(self.lock.release(LockMode::Exclusive, self.range));
     }
 }
@@ -7,3 +7,21 @@ pub(crate) mod utils;
 pub use read_guard::RwLockReadGuard;
 pub use rw_lock::RwLock;
 pub use write_guard::RwLockWriteGuard;
+
+use crate::liveness::Liveness;
+use crate::range::ByteRange;
+use std::time::SystemTime;
+
+pub(crate) const WHOLE: ByteRange = ByteRange { start: 0, end: 1 };
+
+pub(crate) fn file_id<T>(_file: T) -> std::io::Result<(u64, u64)> {
+    panic!("target unsupported")
+}
+
+pub(crate) fn process_liveness(_pid: u32, _started: Option<SystemTime>) -> Liveness {
+    panic!("target unsupported")
+}
+
+pub(crate) fn pid_namespace() -> Option<u64> {
+    panic!("target unsupported")
+}
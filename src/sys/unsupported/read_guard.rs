@@ -1,7 +1,8 @@
-use std::ops;
 use std::os::unix::io::AsRawFd;
+use std::{io, ops};
 
 use super::RwLock;
+use crate::range::ByteRange;
 
 #[derive(Debug)]
 pub struct RwLockReadGuard<'lock, T: AsRawFd> {
@@ -9,7 +10,11 @@ pub struct RwLockReadGuard<'lock, T: AsRawFd> {
 }
 
 impl<'lock, T: AsRawFd> RwLockReadGuard<'lock, T> {
-    pub(crate) fn new(lock: &'lock RwLock<T>) -> Self {
+    pub(crate) fn new(lock: &'lock RwLock<T>, range: ByteRange) -> Self {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn unlock(&self) -> io::Result<()> {
         panic!("target unsupported")
     }
 }
@@ -1,7 +1,14 @@
 use std::io::{self, Error, ErrorKind};
 use std::os::unix::io::AsRawFd;
+use std::time::Instant;
 
 use super::{RwLockReadGuard, RwLockWriteGuard};
+use crate::cancel_token::CancelToken;
+use crate::interrupt_policy::InterruptPolicy;
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::Backend;
 
 #[derive(Debug)]
 pub struct RwLock<T: AsRawFd> {
@@ -15,12 +22,17 @@ impl<T: AsRawFd> RwLock<T> {
     }
 
     #[inline]
-    pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
+    pub fn with_backend(inner: T, backend: Backend) -> Self {
+        panic!("target unsupported")
+    }
+
+    #[inline]
+    pub fn backend(&self) -> Backend {
         panic!("target unsupported")
     }
 
     #[inline]
-    pub fn try_write(&mut self) -> Result<RwLockWriteGuard<'_, T>, Error> {
+    pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
         panic!("target unsupported")
     }
 
@@ -30,7 +42,12 @@ impl<T: AsRawFd> RwLock<T> {
     }
 
     #[inline]
-    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, Error> {
+    pub fn write_range(&mut self, range: ByteRange) -> io::Result<RwLockWriteGuard<'_, T>> {
+        panic!("target unsupported")
+    }
+
+    #[inline]
+    pub fn read_range(&self, range: ByteRange) -> io::Result<RwLockReadGuard<'_, T>> {
         panic!("target unsupported")
     }
 
@@ -41,4 +58,59 @@ impl<T: AsRawFd> RwLock<T> {
     {
         panic!("target unsupported")
     }
+
+    pub(crate) fn lock(&self, mode: LockMode, range: ByteRange, blocking: bool) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn unlock(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn release(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn lock_with(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        policy: InterruptPolicy,
+    ) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn lock_until(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        deadline: Instant,
+    ) -> Option<io::Result<()>> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn set_interrupt_policy(&self, policy: InterruptPolicy) {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn lock_cancellable(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        token: &CancelToken,
+    ) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn upgrade(&self, range: ByteRange) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn conflicting_lock(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+    ) -> io::Result<Option<LockHolder>> {
+        panic!("target unsupported")
+    }
 }
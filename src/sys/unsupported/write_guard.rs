@@ -1,7 +1,8 @@
-use std::ops;
 use std::os::unix::io::AsRawFd;
+use std::{io, ops};
 
-use super::RwLock;
+use super::{RwLock, RwLockReadGuard};
+use crate::range::ByteRange;
 
 #[derive(Debug)]
 pub struct RwLockWriteGuard<'lock, T: AsRawFd> {
@@ -9,7 +10,17 @@ pub struct RwLockWriteGuard<'lock, T: AsRawFd> {
 }
 
 impl<'lock, T: AsRawFd> RwLockWriteGuard<'lock, T> {
-    pub(crate) fn new(lock: &'lock mut RwLock<T>) -> Self {
+    pub(crate) fn new(lock: &'lock mut RwLock<T>, range: ByteRange) -> Self {
+        panic!("target unsupported")
+    }
+
+    pub(crate) fn unlock(&self) -> io::Result<()> {
+        panic!("target unsupported")
+    }
+}
+
+impl<'lock, T: AsRawFd> RwLockWriteGuard<'lock, T> {
+    pub(crate) fn downgrade(self) -> io::Result<RwLockReadGuard<'lock, T>> {
         panic!("target unsupported")
     }
 }
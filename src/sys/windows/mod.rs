@@ -1,8 +1,48 @@
 mod read_guard;
 mod rw_lock;
+mod state;
 mod utils;
 mod write_guard;
 
 pub use read_guard::RwLockReadGuard;
 pub use rw_lock::RwLock;
 pub use write_guard::RwLockWriteGuard;
+
+use crate::liveness::Liveness;
+use crate::range::ByteRange;
+use std::time::SystemTime;
+
+/// The region locked by whole-file locks: the first byte of the file.
+pub(crate) const WHOLE: ByteRange = ByteRange { start: 0, end: 1 };
+
+/// Returns the volume serial number and file index of an open file, which
+/// identify it.
+pub(crate) fn file_id<T: std::os::windows::io::AsHandle>(file: T) -> std::io::Result<(u64, u64)> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::HANDLE;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let handle = file.as_handle().as_raw_handle() as HANDLE;
+    // SAFETY: `BY_HANDLE_FILE_INFORMATION` is a plain C struct for which all
+    // zeroes is valid, and the handle is borrowed for the duration of the
+    // call.
+    let info = unsafe {
+        let mut info: BY_HANDLE_FILE_INFORMATION = std::mem::zeroed();
+        utils::syscall(GetFileInformationByHandle(handle, &mut info))?;
+        info
+    };
+    let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+    Ok((u64::from(info.dwVolumeSerialNumber), index))
+}
+
+/// Processes can't be checked on Windows yet.
+pub(crate) fn process_liveness(_pid: u32, _started: Option<SystemTime>) -> Liveness {
+    Liveness::Unknown
+}
+
+/// Windows has no PID namespaces.
+pub(crate) fn pid_namespace() -> Option<u64> {
+    None
+}
@@ -1,16 +1,28 @@
 use std::os::windows::io::AsHandle;
-use windows_sys::Win32::Foundation::HANDLE;
-use windows_sys::Win32::Storage::FileSystem::UnlockFile;
 
-use std::ops;
-use std::os::windows::prelude::*;
+use std::{io, ops};
 
-use super::utils::syscall;
 use super::RwLock;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::unlock;
 
 #[derive(Debug)]
 pub struct RwLockReadGuard<'lock, T: AsHandle> {
-    pub(crate) lock: &'lock RwLock<T>,
+    lock: &'lock RwLock<T>,
+    range: ByteRange,
+}
+
+impl<'lock, T: AsHandle> RwLockReadGuard<'lock, T> {
+    pub(crate) fn new(lock: &'lock RwLock<T>, range: ByteRange) -> Self {
+        Self { lock, range }
+    }
+
+    /// Unlocks the range without consuming the guard. The caller must forget
+    /// the guard if this succeeds.
+    pub(crate) fn unlock(&self) -> io::Result<()> {
+        self.lock.unlock(LockMode::Shared, self.range)
+    }
 }
 
 impl<T: AsHandle> ops::Deref for RwLockReadGuard<'_, T> {
@@ -25,7 +37,6 @@ impl<T: AsHandle> ops::Deref for RwLockReadGuard<'_, T> {
 impl<T: AsHandle> Drop for RwLockReadGuard<'_, T> {
     #[inline]
     fn drop(&mut self) {
-        let handle = self.lock.inner.as_handle().as_raw_handle() as HANDLE;
-        let _ = syscall(unsafe { UnlockFile(handle, 0, 0, 1, 0) });
+        unlock::report(self.lock.release(LockMode::Shared, self.range));
     }
 }
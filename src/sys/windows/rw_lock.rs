@@ -1,74 +1,70 @@
 use std::io::{self, Error, ErrorKind};
-use std::os::windows::io::{AsHandle, AsRawHandle};
+use std::os::windows::io::{AsHandle, AsRawHandle, OwnedHandle};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use windows_sys::Win32::Foundation::ERROR_LOCK_VIOLATION;
 use windows_sys::Win32::Foundation::HANDLE;
 use windows_sys::Win32::Storage::FileSystem::{
-    LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY,
+    LockFileEx, UnlockFile, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY,
 };
 
+use super::state::State;
 use super::utils::{syscall, Overlapped};
-use super::{RwLockReadGuard, RwLockWriteGuard};
+use super::{RwLockReadGuard, RwLockWriteGuard, WHOLE};
+use crate::cancel_token::{self, CancelToken};
+use crate::interrupt_policy::InterruptPolicy;
+use crate::lock_holder::LockHolder;
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::Backend;
 
 #[derive(Debug)]
 pub struct RwLock<T: AsHandle> {
     pub(crate) inner: T,
+    state: Arc<State>,
 }
 
 impl<T: AsHandle> RwLock<T> {
     #[inline]
     pub fn new(inner: T) -> Self {
-        RwLock { inner }
+        RwLock {
+            inner,
+            state: Arc::default(),
+        }
     }
 
     #[inline]
-    pub fn read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
-        // See: https://stackoverflow.com/a/9186532, https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-lockfileex
-        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
-        let overlapped = Overlapped::zero();
-        let flags = 0;
-        syscall(unsafe { LockFileEx(handle, flags, 0, 1, 0, overlapped.raw()) })?;
-        Ok(RwLockReadGuard { lock: self })
+    pub fn with_backend(inner: T, _backend: Backend) -> Self {
+        Self::new(inner)
     }
 
     #[inline]
-    pub fn try_read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
-        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
-        let overlapped = Overlapped::zero();
-        let flags = LOCKFILE_FAIL_IMMEDIATELY;
+    pub fn backend(&self) -> Backend {
+        Backend::Native
+    }
 
-        syscall(unsafe { LockFileEx(handle, flags, 0, 1, 0, overlapped.raw()) }).map_err(
-            |error| match error.raw_os_error().map(|error_code| error_code as u32) {
-                Some(ERROR_LOCK_VIOLATION) => Error::from(ErrorKind::WouldBlock),
-                _ => error,
-            },
-        )?;
-        Ok(RwLockReadGuard { lock: self })
+    #[inline]
+    pub fn read(&self) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.read_range(WHOLE)
     }
 
     #[inline]
     pub fn write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
-        // See: https://stackoverflow.com/a/9186532, https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-lockfileex
-        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
-        let overlapped = Overlapped::zero();
-        let flags = LOCKFILE_EXCLUSIVE_LOCK;
-        syscall(unsafe { LockFileEx(handle, flags, 0, 1, 0, overlapped.raw()) })?;
-        Ok(RwLockWriteGuard { lock: self })
+        self.write_range(WHOLE)
     }
 
     #[inline]
-    pub fn try_write(&mut self) -> io::Result<RwLockWriteGuard<'_, T>> {
-        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
-        let overlapped = Overlapped::zero();
-        let flags = LOCKFILE_FAIL_IMMEDIATELY | LOCKFILE_EXCLUSIVE_LOCK;
+    pub fn read_range(&self, range: ByteRange) -> io::Result<RwLockReadGuard<'_, T>> {
+        self.lock(LockMode::Shared, range, true)?;
+        Ok(RwLockReadGuard::new(self, range))
+    }
 
-        syscall(unsafe { LockFileEx(handle, flags, 0, 1, 0, overlapped.raw()) }).map_err(
-            |error| match error.raw_os_error().map(|error_code| error_code as u32) {
-                Some(ERROR_LOCK_VIOLATION) => Error::from(ErrorKind::WouldBlock),
-                _ => error,
-            },
-        )?;
-        Ok(RwLockWriteGuard { lock: self })
+    #[inline]
+    pub fn write_range(&mut self, range: ByteRange) -> io::Result<RwLockWriteGuard<'_, T>> {
+        self.lock(LockMode::Exclusive, range, true)?;
+        Ok(RwLockWriteGuard::new(self, range))
     }
 
     #[inline]
@@ -78,4 +74,169 @@ impl<T: AsHandle> RwLock<T> {
     {
         self.inner
     }
+
+    /// Creates a second handle to this lock, backed by a duplicate of its
+    /// file handle.
+    ///
+    /// Duplicated handles share the same file object and the same
+    /// bookkeeping, so locks taken through one can be released through the
+    /// other.
+    #[cfg_attr(not(any(feature = "async", feature = "tokio")), allow(dead_code))]
+    pub(crate) fn detach(&self) -> io::Result<RwLock<OwnedHandle>> {
+        Ok(RwLock {
+            inner: self.inner.as_handle().try_clone_to_owned()?,
+            state: Arc::clone(&self.state),
+        })
+    }
+
+    /// Locks a range without creating a guard for it.
+    ///
+    /// Windows keeps a separate lock for every call, so every shared lock is
+    /// released by its own unlock. Locks of this handle which conflict with
+    /// the range are waited for first, as Windows lets them stack.
+    pub(crate) fn lock(&self, mode: LockMode, range: ByteRange, blocking: bool) -> io::Result<()> {
+        self.state.reserve(mode, range, blocking)?;
+        let result = self.lock_file(mode, range, blocking);
+        if result.is_err() {
+            self.state.forget(mode, range);
+        }
+        result
+    }
+
+    /// Locks a range with `LockFileEx`, regardless of the locks this handle
+    /// holds.
+    fn lock_file(&self, mode: LockMode, range: ByteRange, blocking: bool) -> io::Result<()> {
+        let mut flags = 0;
+        if mode == LockMode::Exclusive {
+            flags |= LOCKFILE_EXCLUSIVE_LOCK;
+        }
+        if !blocking {
+            flags |= LOCKFILE_FAIL_IMMEDIATELY;
+        }
+
+        // See: https://stackoverflow.com/a/9186532, https://docs.microsoft.com/en-us/windows/win32/api/fileapi/nf-fileapi-lockfileex
+        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
+        let overlapped = Overlapped::at(range.start);
+        let (len_low, len_high) = split(range.len());
+
+        syscall(unsafe { LockFileEx(handle, flags, 0, len_low, len_high, overlapped.raw()) })
+            .map_err(
+                |error| match error.raw_os_error().map(|error_code| error_code as u32) {
+                    Some(ERROR_LOCK_VIOLATION) => Error::from(ErrorKind::WouldBlock),
+                    _ => error,
+                },
+            )
+    }
+
+    /// Converts the exclusive lock on a range into a shared one.
+    ///
+    /// A handle may take a shared lock on a range it holds exclusively, and
+    /// the first unlock then releases the exclusive lock, so the range is
+    /// never unlocked in between.
+    pub(crate) fn downgrade(&self, range: ByteRange) -> io::Result<()> {
+        self.lock_file(LockMode::Shared, range, true)?;
+        self.unlock_file(range)?;
+        self.state.downgrade(range);
+        Ok(())
+    }
+
+    /// Converts a shared lock on a range into an exclusive one. If the
+    /// conversion fails the range is left unlocked.
+    ///
+    /// An exclusive lock can't overlap the handle's own shared lock, so the
+    /// shared lock is released first, letting another writer in.
+    pub(crate) fn upgrade(&self, range: ByteRange) -> io::Result<()> {
+        self.unlock(LockMode::Shared, range)?;
+        self.lock(LockMode::Exclusive, range, true)
+    }
+
+    /// Unlocks a range previously locked with [`RwLock::lock`]. If unlocking
+    /// fails the range is still considered held, so it can be retried.
+    pub(crate) fn unlock(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        self.unlock_file(range)?;
+        self.state.forget(mode, range);
+        Ok(())
+    }
+
+    /// Unlocks a range for good. If unlocking fails the range is forgotten
+    /// anyway, so other threads of this process don't wait for it forever.
+    #[inline]
+    pub(crate) fn release(&self, mode: LockMode, range: ByteRange) -> io::Result<()> {
+        let result = self.unlock_file(range);
+        self.state.forget(mode, range);
+        result
+    }
+
+    /// Unlocks a range with `UnlockFile`.
+    fn unlock_file(&self, range: ByteRange) -> io::Result<()> {
+        let handle = self.inner.as_handle().as_raw_handle() as HANDLE;
+        let (offset_low, offset_high) = split(range.start);
+        let (len_low, len_high) = split(range.len());
+        syscall(unsafe { UnlockFile(handle, offset_low, offset_high, len_low, len_high) })
+    }
+
+    /// `LockFileEx` isn't interrupted by anything, so there's nothing to
+    /// retry.
+    #[inline]
+    pub(crate) fn lock_with(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        _policy: InterruptPolicy,
+    ) -> io::Result<()> {
+        self.lock(mode, range, true)
+    }
+
+    /// `LockFileEx` has no timeout, so the caller has to poll.
+    #[inline]
+    pub(crate) fn lock_until(
+        &self,
+        _mode: LockMode,
+        _range: ByteRange,
+        _deadline: Instant,
+    ) -> Option<io::Result<()>> {
+        None
+    }
+
+    #[inline]
+    pub(crate) fn set_interrupt_policy(&self, _policy: InterruptPolicy) {}
+
+    /// `LockFileEx` can't be interrupted, so this polls the lock, backing
+    /// off exponentially up to a few tens of milliseconds.
+    pub(crate) fn lock_cancellable(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        token: &CancelToken,
+    ) -> io::Result<()> {
+        let mut backoff = Duration::from_millis(1);
+        loop {
+            if token.is_cancelled() {
+                return Err(cancel_token::cancelled());
+            }
+            match self.lock(mode, range, false) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                result => return result,
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_millis(50));
+        }
+    }
+
+    /// Windows has no way to ask who holds a lock.
+    pub(crate) fn conflicting_lock(
+        &self,
+        _mode: LockMode,
+        _range: ByteRange,
+    ) -> io::Result<Option<LockHolder>> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "querying lock holders is not supported on this platform",
+        ))
+    }
+}
+
+/// Split a 64-bit value into its low and high 32-bit halves.
+fn split(value: u64) -> (u32, u32) {
+    (value as u32, (value >> 32) as u32)
 }
@@ -0,0 +1,74 @@
+use std::io::{self, ErrorKind};
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+
+/// The locks held through one file handle, and the handles duplicated from
+/// it.
+///
+/// `LockFileEx` never makes a handle wait for its own locks: a shared lock
+/// stacks on an exclusive one, and the next unlock of the range releases the
+/// exclusive lock first. This keeps track of which ranges are held, so a
+/// reader of the same lock waits for a writer instead of getting in beside it
+/// and unlocking the writer's lock when it leaves.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    held: Mutex<Vec<(LockMode, ByteRange)>>,
+    /// Notified whenever a range is removed from `held`.
+    released: Condvar,
+}
+
+impl State {
+    /// Reserves a range before it is locked, waiting while another lock of
+    /// the handle conflicts with it, or failing right away if the caller
+    /// doesn't want to block.
+    pub(crate) fn reserve(
+        &self,
+        mode: LockMode,
+        range: ByteRange,
+        blocking: bool,
+    ) -> io::Result<()> {
+        let mut held = self.held();
+        while held.iter().any(|(held_mode, held)| {
+            (mode == LockMode::Exclusive || *held_mode == LockMode::Exclusive)
+                && held.overlaps(&range)
+        }) {
+            if !blocking {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+            held = self
+                .released
+                .wait(held)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        held.push((mode, range));
+        Ok(())
+    }
+
+    /// Forgets a held or reserved range, and wakes up threads waiting for
+    /// it.
+    pub(crate) fn forget(&self, mode: LockMode, range: ByteRange) {
+        let mut held = self.held();
+        if let Some(index) = held.iter().position(|held| *held == (mode, range)) {
+            held.swap_remove(index);
+        }
+        self.released.notify_all();
+    }
+
+    /// Records that the exclusive lock on a range became a shared one.
+    pub(crate) fn downgrade(&self, range: ByteRange) {
+        let mut held = self.held();
+        let exclusive = (LockMode::Exclusive, range);
+        if let Some(held) = held.iter_mut().find(|held| **held == exclusive) {
+            held.0 = LockMode::Shared;
+        }
+        self.released.notify_all();
+    }
+
+    /// The ranges only change in one step, so a poisoned mutex still holds
+    /// accurate ones.
+    fn held(&self) -> MutexGuard<'_, Vec<(LockMode, ByteRange)>> {
+        self.held.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -2,7 +2,7 @@ use std::io;
 use std::mem;
 
 use windows_sys::Win32::Foundation::BOOL;
-use windows_sys::Win32::System::IO::OVERLAPPED;
+use windows_sys::Win32::System::IO::{OVERLAPPED, OVERLAPPED_0, OVERLAPPED_0_0};
 
 /// A wrapper around `OVERLAPPED` to provide "rustic" accessors and
 /// initializers.
@@ -17,6 +17,19 @@ impl Overlapped {
         Overlapped(unsafe { mem::zeroed() })
     }
 
+    /// Creates a new zeroed out instance whose operation starts at the
+    /// given file offset.
+    pub(crate) fn at(offset: u64) -> Overlapped {
+        let mut overlapped = Self::zero();
+        overlapped.0.Anonymous = OVERLAPPED_0 {
+            Anonymous: OVERLAPPED_0_0 {
+                Offset: offset as u32,
+                OffsetHigh: (offset >> 32) as u32,
+            },
+        };
+        overlapped
+    }
+
     /// Gain access to the raw underlying data
     pub(crate) fn raw(&self) -> *mut OVERLAPPED {
         &self.0 as *const _ as *mut _
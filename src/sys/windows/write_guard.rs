@@ -1,16 +1,45 @@
 use std::os::windows::io::AsHandle;
-use windows_sys::Win32::Foundation::HANDLE;
-use windows_sys::Win32::Storage::FileSystem::UnlockFile;
 
-use std::ops;
-use std::os::windows::prelude::*;
+use std::mem::ManuallyDrop;
+use std::{io, ops, ptr};
 
-use super::utils::syscall;
-use super::RwLock;
+use super::{RwLock, RwLockReadGuard};
+use crate::lock_mode::LockMode;
+use crate::range::ByteRange;
+use crate::unlock;
 
 #[derive(Debug)]
 pub struct RwLockWriteGuard<'lock, T: AsHandle> {
-    pub(crate) lock: &'lock mut RwLock<T>,
+    lock: &'lock mut RwLock<T>,
+    range: ByteRange,
+}
+
+impl<'lock, T: AsHandle> RwLockWriteGuard<'lock, T> {
+    pub(crate) fn new(lock: &'lock mut RwLock<T>, range: ByteRange) -> Self {
+        Self { lock, range }
+    }
+
+    /// Unlocks the range without consuming the guard. The caller must forget
+    /// the guard if this succeeds.
+    pub(crate) fn unlock(&self) -> io::Result<()> {
+        self.lock.unlock(LockMode::Exclusive, self.range)
+    }
+
+    /// Converts this guard into a read guard for the same range. If the
+    /// conversion fails the lock is released.
+    pub(crate) fn downgrade(self) -> io::Result<RwLockReadGuard<'lock, T>> {
+        let guard = ManuallyDrop::new(self);
+        // SAFETY: `guard` is never used or dropped again, so the reference is
+        // moved out of it exactly once.
+        let lock: &'lock mut RwLock<T> = unsafe { ptr::read(&guard.lock) };
+        match lock.downgrade(guard.range) {
+            Ok(()) => Ok(RwLockReadGuard::new(lock, guard.range)),
+            Err(err) => {
+                unlock::report(lock.release(LockMode::Exclusive, guard.range));
+                Err(err)
+            }
+        }
+    }
 }
 
 impl<T: AsHandle> ops::Deref for RwLockWriteGuard<'_, T> {
@@ -32,7 +61,6 @@ impl<T: AsHandle> ops::DerefMut for RwLockWriteGuard<'_, T> {
 impl<T: AsHandle> Drop for RwLockWriteGuard<'_, T> {
     #[inline]
     fn drop(&mut self) {
-        let handle = self.lock.inner.as_handle().as_raw_handle() as HANDLE;
-        let _ = syscall(unsafe { UnlockFile(handle, 0, 0, 1, 0) });
+        unlock::report(self.lock.release(LockMode::Exclusive, self.range));
     }
 }
@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::sync::{PoisonError, RwLock};
+use std::{fmt, io};
+
+/// A function which receives the errors of guards which failed to unlock when
+/// dropped.
+type Hook = Box<dyn Fn(&io::Error) + Send + Sync + 'static>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Registers a hook which is called whenever a guard fails to unlock its file
+/// when it is dropped, replacing the previous hook.
+///
+/// Dropping a guard can't return an error, so by default unlock errors are
+/// ignored. Call `unlock` on the guard instead to handle the error in place.
+///
+/// The hook is shared by the whole process and may be called from any thread.
+/// It must not call [`set_unlock_hook`] or [`take_unlock_hook`] itself.
+///
+/// # Examples
+///
+/// ```
+/// fd_lock::set_unlock_hook(Box::new(|err| {
+///     eprintln!("failed to unlock a file: {err}");
+/// }));
+/// ```
+pub fn set_unlock_hook(hook: Hook) {
+    *HOOK.write().unwrap_or_else(PoisonError::into_inner) = Some(hook);
+}
+
+/// Unregisters the hook set with [`set_unlock_hook`], returning it.
+pub fn take_unlock_hook() -> Option<Hook> {
+    HOOK.write().unwrap_or_else(PoisonError::into_inner).take()
+}
+
+/// Passes the error of an implicit unlock to the hook, if there is one.
+pub(crate) fn report(result: io::Result<()>) {
+    if let Err(err) = result {
+        if let Some(hook) = &*HOOK.read().unwrap_or_else(PoisonError::into_inner) {
+            hook(&err);
+        }
+    }
+}
+
+/// The error returned when a guard fails to unlock its file.
+///
+/// The guard is handed back, still holding the lock, so unlocking can be
+/// retried. Dropping it tries to unlock once more and reports any error to
+/// the hook registered with [`set_unlock_hook`].
+pub struct UnlockError<G> {
+    guard: G,
+    error: io::Error,
+}
+
+impl<G> UnlockError<G> {
+    pub(crate) fn new(guard: G, error: io::Error) -> Self {
+        Self { guard, error }
+    }
+
+    /// Returns the error which made unlocking fail.
+    #[inline]
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Returns the guard, so unlocking can be retried.
+    #[inline]
+    pub fn into_guard(self) -> G {
+        self.guard
+    }
+
+    /// Returns both the guard and the error.
+    #[inline]
+    pub fn into_parts(self) -> (G, io::Error) {
+        (self.guard, self.error)
+    }
+}
+
+impl<G> fmt::Debug for UnlockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlockError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<G> fmt::Display for UnlockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to unlock the file: {}", self.error)
+    }
+}
+
+impl<G> Error for UnlockError<G> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Drops the guard, which tries to unlock once more, and keeps the error.
+impl<G> From<UnlockError<G>> for io::Error {
+    fn from(err: UnlockError<G>) -> Self {
+        err.error
+    }
+}
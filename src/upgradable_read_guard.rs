@@ -0,0 +1,95 @@
+use std::mem::ManuallyDrop;
+use std::{io, ops, ptr};
+
+use crate::lock_mode::LockMode;
+use crate::range::UPGRADE;
+use crate::read_guard::RwLockReadGuard;
+use crate::sys;
+use crate::unlock;
+use crate::write_guard::RwLockWriteGuard;
+
+/// RAII structure used to release the upgradable read access of a lock when
+/// dropped.
+///
+/// An upgradable read guard shares the file with plain readers, but at most
+/// one upgradable reader holds the lock at a time, across all processes. It
+/// can later be upgraded to a [`RwLockWriteGuard`] without another upgradable
+/// reader getting in first.
+///
+/// This structure is created by the [`upgradable_read`] and
+/// [`try_upgradable_read`] methods on [`RwLock`].
+///
+/// [`upgradable_read`]: crate::RwLock::upgradable_read
+/// [`try_upgradable_read`]: crate::RwLock::try_upgradable_read
+/// [`RwLock`]: crate::RwLock
+#[must_use = "if unused the RwLock will immediately unlock"]
+#[derive(Debug)]
+pub struct RwLockUpgradableReadGuard<'lock, T: sys::AsOpenFile> {
+    lock: &'lock mut sys::RwLock<T>,
+}
+
+impl<'lock, T: sys::AsOpenFile> RwLockUpgradableReadGuard<'lock, T> {
+    pub(crate) fn new(lock: &'lock mut sys::RwLock<T>) -> Self {
+        Self { lock }
+    }
+
+    /// Upgrades this guard to exclusive write access, blocking the current
+    /// thread until all other readers have released the lock.
+    ///
+    /// No other upgradable reader can acquire the lock in the meantime. Plain
+    /// writers are kept out as well on backends which convert locks
+    /// atomically: Linux [`Backend::Ofd`] locks. On Windows the shared lock is
+    /// released before the exclusive lock is taken, so a plain writer may get
+    /// in first, in which case this call blocks until that writer is done.
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be upgraded the error is returned and the lock
+    /// is released.
+    ///
+    /// [`Backend::Ofd`]: crate::Backend
+    #[inline]
+    pub fn upgrade(self) -> io::Result<RwLockWriteGuard<'lock, T>> {
+        let lock = self.into_lock();
+        let result = lock.upgrade(sys::WHOLE);
+        unlock::report(lock.release(LockMode::Exclusive, UPGRADE));
+        result?;
+        let guard = sys::RwLockWriteGuard::new(lock, sys::WHOLE);
+        Ok(RwLockWriteGuard::new(guard))
+    }
+
+    /// Converts this guard into a plain read guard, letting another
+    /// upgradable reader in.
+    #[inline]
+    pub fn downgrade(self) -> RwLockReadGuard<'lock, T> {
+        let lock = self.into_lock();
+        unlock::report(lock.release(LockMode::Exclusive, UPGRADE));
+        RwLockReadGuard::new(sys::RwLockReadGuard::new(lock, sys::WHOLE))
+    }
+
+    /// Takes the lock out of this guard without releasing it.
+    fn into_lock(self) -> &'lock mut sys::RwLock<T> {
+        let guard = ManuallyDrop::new(self);
+        // SAFETY: `guard` is never used or dropped again, so the reference is
+        // moved out of it exactly once.
+        unsafe { ptr::read(&guard.lock) }
+    }
+}
+
+impl<T: sys::AsOpenFile> ops::Deref for RwLockUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.lock.inner
+    }
+}
+
+/// Release the lock.
+impl<T: sys::AsOpenFile> Drop for RwLockUpgradableReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unlock::report(self.lock.release(LockMode::Shared, sys::WHOLE));
+        unlock::report(self.lock.release(LockMode::Exclusive, UPGRADE));
+    }
+}
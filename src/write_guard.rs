@@ -1,15 +1,19 @@
-use std::ops;
+use std::{io, mem, ops};
 
+use crate::read_guard::RwLockReadGuard;
 use crate::sys;
+use crate::unlock::UnlockError;
 
 /// RAII structure used to release the exclusive write access of a lock when
 /// dropped.
 ///
-/// This structure is created by the [`write`] and [`try_write`] methods
-/// on [`RwLock`].
+/// This structure is created by the [`write`], [`try_write`],
+/// [`write_range`] and [`try_write_range`] methods on [`RwLock`].
 ///
 /// [`write`]: crate::RwLock::write
 /// [`try_write`]: crate::RwLock::try_write
+/// [`write_range`]: crate::RwLock::write_range
+/// [`try_write_range`]: crate::RwLock::try_write_range
 /// [`RwLock`]: crate::RwLock
 #[must_use = "if unused the RwLock will immediately unlock"]
 #[derive(Debug)]
@@ -21,6 +25,86 @@ impl<'lock, T: sys::AsOpenFile> RwLockWriteGuard<'lock, T> {
     pub(crate) fn new(guard: sys::RwLockWriteGuard<'lock, T>) -> Self {
         Self { guard }
     }
+
+    /// Releases the exclusive write access, returning the error if unlocking
+    /// fails.
+    ///
+    /// Dropping the guard releases it as well, but has no way to report an
+    /// error other than the hook registered with [`set_unlock_hook`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     let mut guard = f.write()?;
+    ///     write!(guard, "chashu cat")?;
+    ///     guard.unlock()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If unlocking fails the guard is returned with the error, still holding
+    /// the lock, so unlocking can be retried.
+    ///
+    /// [`set_unlock_hook`]: crate::set_unlock_hook
+    #[inline]
+    pub fn unlock(self) -> Result<(), UnlockError<Self>> {
+        match self.guard.unlock() {
+            Ok(()) => {
+                mem::forget(self);
+                Ok(())
+            }
+            Err(err) => Err(UnlockError::new(self, err)),
+        }
+    }
+
+    /// Converts this write guard into a read guard, keeping other readers
+    /// out of the locked bytes for as long as possible.
+    ///
+    /// Whether another process can acquire the lock while it's being
+    /// converted depends on the backend:
+    ///
+    /// - Linux [`Backend::Ofd`] locks, Solaris `fcntl` locks and Windows
+    ///   locks are converted atomically: the lock is never released, so no
+    ///   writer can get in between.
+    /// - `flock(2)`, the default on every other Unix, releases the exclusive
+    ///   lock before taking the shared one. Another writer may briefly
+    ///   acquire the lock in between, in which case this call blocks until
+    ///   that writer is done.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use fd_lock::RwLock;
+    /// use std::fs::File;
+    /// use std::io::prelude::*;
+    ///
+    /// fn main() -> std::io::Result<()> {
+    ///     let mut f = RwLock::new(File::open("foo.txt")?);
+    ///     let mut guard = f.write()?;
+    ///     write!(guard, "chashu cat")?;
+    ///     let guard = guard.downgrade()?;
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the lock could not be converted the error is returned and the lock
+    /// is released.
+    ///
+    /// [`Backend::Ofd`]: crate::Backend
+    #[inline]
+    pub fn downgrade(self) -> io::Result<RwLockReadGuard<'lock, T>> {
+        Ok(RwLockReadGuard::new(self.guard.downgrade()?))
+    }
 }
 
 impl<T: sys::AsOpenFile> ops::Deref for RwLockWriteGuard<'_, T> {
@@ -38,9 +122,3 @@ impl<T: sys::AsOpenFile> ops::DerefMut for RwLockWriteGuard<'_, T> {
         self.guard.deref_mut()
     }
 }
-
-/// Release the lock.
-impl<T: sys::AsOpenFile> Drop for RwLockWriteGuard<'_, T> {
-    #[inline]
-    fn drop(&mut self) {}
-}
@@ -0,0 +1,44 @@
+//! The `SIGURG` disposition applies to the whole process, and is only
+//! checked once, so these tests run in their own test binary.
+
+#![cfg(unix)]
+
+use fd_lock::{CancelToken, RwLock};
+use std::fs::File;
+use std::time::Duration;
+use std::{mem, ptr, thread};
+
+use tempfile::tempdir;
+
+#[test]
+fn cancel_with_a_restarting_handler() {
+    extern "C" fn handler(_: libc::c_int) {}
+
+    // SAFETY: `sigaction` is a plain C struct for which all zeroes is valid,
+    // and the handler does nothing, so it is async-signal-safe.
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        assert_eq!(libc::sigaction(libc::SIGURG, &action, ptr::null_mut()), 0);
+    }
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(&path).unwrap());
+    let _g0 = l0.write().unwrap();
+
+    let token = CancelToken::new();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| l1.write_cancellable(&token).map(drop));
+        thread::sleep(Duration::from_millis(50));
+        // The signal can't interrupt `flock`, so the waiter has to poll.
+        token.cancel();
+        let err = waiter.join().unwrap().unwrap_err();
+        assert!(matches!(
+            fd_lock::Error::from(err),
+            fd_lock::Error::Cancelled(_)
+        ));
+    });
+}
@@ -0,0 +1,94 @@
+//! The same-process policy applies to the whole process, so these tests run
+//! in their own test binary.
+
+#![cfg(unix)]
+
+use fd_lock::{RwLock, SameProcessPolicy};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+use tempfile::tempdir;
+
+/// Held by each test, as they change the policy of the whole process.
+static POLICY: Mutex<()> = Mutex::new(());
+
+#[test]
+fn same_process_conflicts() {
+    let _policy = POLICY.lock().unwrap_or_else(PoisonError::into_inner);
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(&path).unwrap());
+
+    fd_lock::set_same_process_policy(SameProcessPolicy::Error);
+    let g0 = l0.write().unwrap();
+    let err = l1.read().unwrap_err();
+    assert!(matches!(
+        fd_lock::Error::from(err),
+        fd_lock::Error::Deadlock(_)
+    ));
+    let err = l1.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    drop(g0);
+
+    // Readers of different handles don't conflict.
+    let g0 = l0.read().unwrap();
+    drop(l1.read().unwrap());
+    drop(g0);
+
+    fd_lock::set_same_process_policy(SameProcessPolicy::Wait);
+    let g0 = l0.write().unwrap();
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(g0);
+        });
+        drop(l1.write().unwrap());
+    });
+
+    fd_lock::set_same_process_policy(SameProcessPolicy::Ignore);
+}
+
+#[test]
+fn nested_readers_wait_without_blocking_the_lock() {
+    let _policy = POLICY.lock().unwrap_or_else(PoisonError::into_inner);
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(&path).unwrap());
+
+    fd_lock::set_same_process_policy(SameProcessPolicy::Wait);
+    let g0 = l0.read().unwrap();
+    thread::scope(|s| {
+        let writer = s.spawn(|| drop(l1.write().unwrap()));
+        thread::sleep(Duration::from_millis(50));
+        // A reader of a range the lock already holds, while another handle
+        // waits to write it.
+        let g1 = l0.read().unwrap();
+        drop(g0);
+        drop(g1);
+        writer.join().unwrap();
+    });
+    fd_lock::set_same_process_policy(SameProcessPolicy::Ignore);
+}
+
+#[test]
+fn forgotten_guards_are_forgotten_with_their_lock() {
+    let _policy = POLICY.lock().unwrap_or_else(PoisonError::into_inner);
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    fd_lock::set_same_process_policy(SameProcessPolicy::Error);
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    std::mem::forget(l0.write().unwrap());
+    drop(l0);
+
+    let mut l1 = RwLock::new(File::open(&path).unwrap());
+    drop(l1.write().unwrap());
+    fd_lock::set_same_process_policy(SameProcessPolicy::Ignore);
+}
@@ -1,6 +1,9 @@
-use fd_lock::RwLock;
+use fd_lock::{CancelToken, KeyedLocks, LockFile, LockMode, LockOptions, LockSet, Pidfile, RwLock};
 use std::fs::File;
 use std::io::ErrorKind;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use tempfile::tempdir;
 
@@ -64,6 +67,1275 @@ fn write_and_read_lock() {
     drop(g0);
 }
 
+#[test]
+fn shared_lock_held_until_last_reader() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(path).unwrap());
+
+    let (first_tx, first_rx) = mpsc::channel();
+    let (last_tx, last_rx) = mpsc::channel::<()>();
+
+    thread::scope(|s| {
+        let l0 = &l0;
+        let g0 = l0.read().unwrap();
+        s.spawn(move || {
+            let _g1 = l0.read().unwrap();
+            first_tx.send(()).unwrap();
+            last_rx.recv().unwrap();
+        });
+
+        // Drop the first reader while the other thread still holds its guard.
+        first_rx.recv().unwrap();
+        drop(g0);
+
+        let err = l1.try_write().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        last_tx.send(()).unwrap();
+    });
+
+    let _g = l1.try_write().unwrap();
+}
+
+#[test]
+fn write_timeout_expires() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(path).unwrap());
+
+    let _g0 = l0.try_read().unwrap();
+
+    let start = Instant::now();
+    let err = l1.write_timeout(Duration::from_millis(100)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TimedOut));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+fn timeout_waits_for_other_thread() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = Arc::new(RwLock::new(File::create(path).unwrap()));
+    let g0 = l0.clone().write_owned().unwrap();
+
+    let start = Instant::now();
+    let err = l0.read_timeout(Duration::from_millis(100)).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::TimedOut));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    drop(g0);
+    drop(l0.read_timeout(Duration::ZERO).unwrap());
+}
+
+#[test]
+fn read_timeout_acquires_after_release() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let l1 = RwLock::new(File::open(path).unwrap());
+
+    let g0 = l0.try_write().unwrap();
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(g0);
+        });
+        let _g1 = l1.read_timeout(Duration::from_secs(10)).unwrap();
+    });
+}
+
+#[test]
+fn lock_options_retry_with_backoff() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(path).unwrap());
+
+    let mut options = LockOptions::new();
+    options
+        .env_overrides(false)
+        .retries(3)
+        .spins(1)
+        .jitter(true);
+
+    let g0 = l0.try_write().unwrap();
+    let err = options.read(&l1).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(g0);
+        });
+        let _g1 = LockOptions::new()
+            .env_overrides(false)
+            .max_wait(Duration::from_secs(10))
+            .write(&mut l1)
+            .unwrap();
+    });
+}
+
+#[test]
+fn lock_options_unbounded_wait() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let mut l0 = RwLock::new(File::create(path).unwrap());
+
+    // A maximum wait too long to represent waits without a deadline.
+    let mut options = LockOptions::new();
+    options.env_overrides(false).max_wait(Duration::MAX);
+    drop(options.read(&l0).unwrap());
+    drop(options.blocking(true).write(&mut l0).unwrap());
+}
+
+#[test]
+fn downgrade_write_lock() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let mut l1 = RwLock::new(File::open(path).unwrap());
+
+    let g0 = l0.try_write().unwrap().downgrade().unwrap();
+    drop(l1.try_read().unwrap());
+
+    let err = l1.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    drop(g0);
+    let _g1 = l1.try_write().unwrap();
+}
+
+#[test]
+fn owned_read_guard_moves_across_threads() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = Arc::new(RwLock::new(File::create(&path).unwrap()));
+    let mut l1 = RwLock::new(File::open(path).unwrap());
+
+    let g0 = l0.clone().try_read_owned().unwrap();
+    let (tx, rx) = mpsc::channel::<()>();
+    let handle = thread::spawn(move || {
+        rx.recv().unwrap();
+        drop(g0);
+    });
+
+    let err = l1.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    tx.send(()).unwrap();
+    handle.join().unwrap();
+    drop(l1.try_write().unwrap());
+}
+
+#[test]
+fn owned_write_guard_excludes_same_lock() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = Arc::new(RwLock::new(File::create(&path).unwrap()));
+    let l1 = RwLock::new(File::open(path).unwrap());
+
+    let g0 = l0.clone().write_owned().unwrap();
+    let err = l0.try_read().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    let err = l0.clone().try_write_owned().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    // The failed attempts left the write lock in place.
+    let err = l1.try_read().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    drop(g0);
+    drop(l0.try_read().unwrap());
+}
+
+#[test]
+fn into_write_guard_returns_lock() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let l0 = RwLock::new(File::create(&path).unwrap());
+    let l1 = RwLock::new(File::open(path).unwrap());
+
+    let g0 = l0.into_write_guard().unwrap();
+    let err = l1.try_read().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    let l0 = g0.unlock().unwrap();
+    drop(l1.try_read().unwrap());
+    drop(l0.try_read().unwrap());
+}
+
+#[test]
+fn lock_file_keeps_contents() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+    std::fs::write(&path, "chashu cat").unwrap();
+
+    let mut l0 = LockFile::open(&path).unwrap();
+    drop(l0.try_write().unwrap());
+    assert_eq!(l0.path(), path);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "chashu cat");
+}
+
+#[test]
+fn lock_file_errors_name_path() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let err = LockFile::options().create(false).open(&path).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::NotFound));
+    assert!(err.to_string().contains(&*path.to_string_lossy()));
+
+    let mut l0 = LockFile::open(&path).unwrap();
+    let l1 = LockFile::open(&path).unwrap();
+    let _g1 = l1.try_read().unwrap();
+    let err = l0.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    assert!(err.to_string().contains(&*path.to_string_lossy()));
+}
+
+#[test]
+fn pidfile_single_instance() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("pidfile");
+    std::fs::write(&path, "a stale pid file which is longer").unwrap();
+
+    let p0 = Pidfile::create(&path).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        format!("{}\n", std::process::id())
+    );
+
+    let err = Pidfile::create(&path).unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    let holder = Pidfile::holder(&path).unwrap().unwrap();
+    assert_eq!(holder.pid(), std::process::id());
+    assert_eq!(holder.hostname(), None);
+
+    p0.unlock().unwrap();
+    drop(Pidfile::create(&path).unwrap());
+}
+
+#[test]
+fn pidfile_hostname_and_start_time() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("pidfile");
+
+    let _p0 = Pidfile::options()
+        .hostname(true)
+        .start_time(true)
+        .create(&path)
+        .unwrap();
+    let holder = Pidfile::holder(&path).unwrap().unwrap();
+    assert_eq!(holder.pid(), std::process::id());
+    assert!(holder.hostname().is_some());
+    let started = holder.started().unwrap();
+    assert!(started.elapsed().unwrap() < Duration::from_secs(60));
+
+    assert!(Pidfile::holder(dir.path().join("missing"))
+        .unwrap()
+        .is_none());
+}
+
+/// Removes a named lock file when dropped, even if the test fails.
+struct RemoveOnDrop(std::path::PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn named_locks_share_a_file() {
+    let name = format!("fd-lock-test-{}", std::process::id());
+    let path = RemoveOnDrop(fd_lock::named_path(&name).unwrap());
+    assert_eq!(path.0.file_name().unwrap(), &*format!("{name}.lock"));
+
+    let mut l0 = fd_lock::named(&name).unwrap();
+    let mut l1 = fd_lock::named(&name).unwrap();
+    let g0 = l0.try_write().unwrap();
+    let err = l1.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    drop(g0);
+    drop(l1.try_write().unwrap());
+
+    let escaped = fd_lock::named_path("../passwd").unwrap();
+    assert_eq!(escaped.parent(), path.0.parent());
+    assert_ne!(escaped, fd_lock::named_path("__passwd").unwrap());
+
+    let err = fd_lock::named("").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::InvalidInput));
+}
+
+#[test]
+#[cfg(unix)]
+fn named_scopes_use_their_own_directories() {
+    use fd_lock::NamedScope;
+    use std::os::unix::fs::PermissionsExt;
+
+    let name = format!("fd-lock-scope-test-{}", std::process::id());
+    let user = NamedScope::User.path(&name).unwrap();
+    let path = match NamedScope::System.path(&name) {
+        Ok(path) => RemoveOnDrop(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => return,
+        Err(err) => panic!("{err}"),
+    };
+    assert_ne!(path.0.parent(), user.parent());
+
+    let mut l0 = NamedScope::System.open(&name).unwrap();
+    let mode = std::fs::metadata(&path.0).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o666);
+
+    let mut l1 = NamedScope::System.open(&name).unwrap();
+    let _g0 = l0.try_write().unwrap();
+    let err = l1.try_write().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+}
+
+#[test]
+#[cfg(unix)]
+fn system_named_locks_open_existing_files() {
+    use fd_lock::NamedScope;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let name = format!("fd-lock-existing-test-{}", std::process::id());
+    let path = match NamedScope::System.path(&name) {
+        Ok(path) => RemoveOnDrop(path),
+        Err(err) if err.kind() == ErrorKind::NotFound => return,
+        Err(err) => panic!("{err}"),
+    };
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path.0)
+        .unwrap();
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))
+        .unwrap();
+
+    // The file is opened as it is, not created again.
+    let mut l0 = NamedScope::System.open(&name).unwrap();
+    drop(l0.try_write().unwrap());
+    let mode = std::fs::metadata(&path.0).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn keyed_locks_gc_unheld_files() {
+    let dir = tempdir().unwrap();
+    let k0 = KeyedLocks::new(dir.path().join("locks")).unwrap();
+    let k1 = KeyedLocks::new(k0.dir()).unwrap();
+    assert_eq!(k0.path("a/b").unwrap().parent(), Some(k0.dir()));
+
+    let g0 = k0.write("a/b").unwrap();
+    let err = k1.try_write("a/b").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    let err = k0.try_read("a/b").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    drop(k1.try_write("c").unwrap());
+
+    assert_eq!(k1.gc().unwrap(), 1);
+    assert!(k0.path("a/b").unwrap().exists());
+    assert!(!k0.path("c").unwrap().exists());
+
+    // The file is recreated once the key is locked again.
+    drop(g0);
+    let g1 = k1.read("c").unwrap();
+    drop(k0.read("c").unwrap());
+    assert_eq!(k0.gc().unwrap(), 1);
+    drop(g1);
+    assert!(k0.path("c").unwrap().exists());
+}
+
+#[test]
+fn lock_set_is_all_or_nothing() {
+    let dir = tempdir().unwrap();
+    let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+
+    let mut set = LockSet::new();
+    set.add_path(&b, LockMode::Shared).unwrap();
+    set.add_path(&a, LockMode::Exclusive).unwrap();
+    set.add_path(&b, LockMode::Exclusive).unwrap();
+
+    let mut l0 = LockFile::open(&a).unwrap();
+    let mut l1 = LockFile::open(&b).unwrap();
+    let g1 = l1.try_read().unwrap();
+    let err = set.try_lock().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    drop(l0.try_write().unwrap());
+
+    drop(g1);
+    let guard = set.try_lock().unwrap();
+    assert!(guard.get(2).is_some());
+    let err = l0.try_read().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    let err = l1.try_read().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+    guard.unlock().unwrap();
+    drop(l0.try_write().unwrap());
+    drop(l1.try_write().unwrap());
+}
+
+#[test]
+fn cancel_blocked_acquisition() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+    let mut l0 = RwLock::new(File::create(&path).unwrap());
+    let l1 = RwLock::new(File::create(&path).unwrap());
+    let token = CancelToken::new();
+
+    // Waiting for another open file description, in the kernel.
+    let g0 = l0.write().unwrap();
+    let waiter = thread::spawn({
+        let token = token.clone();
+        move || {
+            let mut l1 = l1;
+            let err = l1.write_cancellable(&token).map(drop).unwrap_err();
+            (l1, err)
+        }
+    });
+    thread::sleep(Duration::from_millis(50));
+    token.cancel();
+    let (mut l1, err) = waiter.join().unwrap();
+    assert!(matches!(
+        fd_lock::Error::from(err),
+        fd_lock::Error::Cancelled(_)
+    ));
+    let err = l1.write_cancellable(&token).map(drop).unwrap_err();
+    assert!(matches!(
+        fd_lock::Error::from(err),
+        fd_lock::Error::Cancelled(_)
+    ));
+    drop(g0);
+    drop(l1.try_write().unwrap());
+
+    // Waiting for another thread of this process.
+    let token = CancelToken::new();
+    let l0 = Arc::new(l0);
+    let g0 = l0.clone().write_owned().unwrap();
+    let waiter = thread::spawn({
+        let (l0, token) = (l0.clone(), token.clone());
+        move || l0.read_cancellable(&token).map(drop)
+    });
+    thread::sleep(Duration::from_millis(50));
+    token.cancel();
+    let err = waiter.join().unwrap().unwrap_err();
+    assert!(matches!(
+        fd_lock::Error::from(err),
+        fd_lock::Error::Cancelled(_)
+    ));
+    drop(g0);
+    drop(Arc::try_unwrap(l0).unwrap().try_write().unwrap());
+}
+
+#[test]
+fn error_classifies_contention() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+    let l0 = LockFile::open(&path).unwrap();
+    let mut l1 = LockFile::open(&path).unwrap();
+
+    let _g0 = l0.try_read().unwrap();
+    let err = fd_lock::Error::from(l1.try_write().unwrap_err());
+    assert!(matches!(err, fd_lock::Error::Contended(_)));
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+    let err = std::io::Error::from(err);
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+    assert!(err.to_string().contains(&*path.to_string_lossy()));
+}
+
+#[cfg(unix)]
+#[test]
+fn single_instance_forwards_args() {
+    use fd_lock::{Instance, SingleInstance};
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let instance = match SingleInstance::acquire_with_args(&path, ["first"]).unwrap() {
+        Instance::Primary(instance) => instance,
+        Instance::Forwarded => panic!("no other instance is running"),
+    };
+
+    let second = path.clone();
+    let handle = thread::spawn(move || {
+        let args = ["second", "--flag", "chashu cat"];
+        SingleInstance::acquire_with_args(second, args).unwrap()
+    });
+    let args = instance.requests().next().unwrap().unwrap();
+    assert_eq!(args, ["second", "--flag", "chashu cat"]);
+    assert!(matches!(handle.join().unwrap(), Instance::Forwarded));
+
+    drop(instance);
+    let third = SingleInstance::acquire_with_args(&path, ["third"]).unwrap();
+    assert!(matches!(third, Instance::Primary(_)));
+}
+
+#[cfg(unix)]
+#[test]
+fn single_instance_drops_stalled_clients() {
+    use fd_lock::{Instance, SingleInstance};
+    use std::os::unix::net::UnixStream;
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let Instance::Primary(instance) = SingleInstance::acquire_with_args(&path, ["first"]).unwrap()
+    else {
+        panic!("no other instance is running");
+    };
+
+    // A client which connects but never sends anything.
+    let _stalled = UnixStream::connect(dir.path().join("lockfile.sock")).unwrap();
+    let second = path.clone();
+    let handle = thread::spawn(move || SingleInstance::acquire_with_args(second, ["second"]));
+
+    // The stalled client is skipped rather than reported.
+    let args = instance.requests().next().unwrap().unwrap();
+    assert_eq!(args, ["second"]);
+    assert!(matches!(
+        handle.join().unwrap().unwrap(),
+        Instance::Forwarded
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn single_instance_reports_unacknowledged_requests() {
+    use fd_lock::{Instance, SingleInstance};
+
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("lockfile");
+
+    let Instance::Primary(instance) = SingleInstance::acquire_with_args(&path, ["first"]).unwrap()
+    else {
+        panic!("no other instance is running");
+    };
+
+    let second = path.clone();
+    let handle = thread::spawn(move || SingleInstance::acquire_with_args(second, ["second"]));
+
+    // Exit without acknowledging the request. It was sent, so it may have
+    // been handled, and must not be sent again.
+    let (stream, _) = instance.listener().accept().unwrap();
+    drop(instance);
+    drop(stream);
+    let err = handle.join().unwrap().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::UnexpectedEof));
+
+    let third = SingleInstance::acquire_with_args(&path, ["third"]).unwrap();
+    assert!(matches!(third, Instance::Primary(_)));
+}
+
+#[cfg(feature = "async")]
+mod async_lock {
+    use super::*;
+    use futures_lite::future;
+
+    #[test]
+    fn write_async_waits_for_release() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(File::create(&path).unwrap());
+        let mut l1 = RwLock::new(File::open(path).unwrap());
+
+        let g0 = l0.write().unwrap();
+        let handle = thread::spawn(move || {
+            let _g1 = future::block_on(l1.write_async()).unwrap();
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+        drop(g0);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn read_async_shares_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(File::create(&path).unwrap());
+        let l1 = RwLock::new(File::open(path).unwrap());
+
+        let _g0 = future::block_on(l0.read_async()).unwrap();
+        let _g1 = future::block_on(l1.read_async()).unwrap();
+    }
+
+    #[test]
+    fn cancelled_write_async_releases_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(File::create(&path).unwrap());
+        let mut l1 = RwLock::new(File::open(path).unwrap());
+
+        let g0 = l0.write().unwrap();
+        let mut acquire = Box::pin(l1.write_async());
+        assert!(future::block_on(future::poll_once(&mut acquire)).is_none());
+        drop(acquire);
+        drop(g0);
+
+        // The abandoned acquisition completes in the background and must
+        // release the lock again.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let _g0 = loop {
+            match l0.try_write() {
+                Ok(guard) => break guard,
+                Err(err) => assert_eq!(err.kind(), ErrorKind::WouldBlock),
+            }
+            assert!(Instant::now() < deadline, "lock was leaked");
+            thread::sleep(Duration::from_millis(10));
+        };
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_lock {
+    use super::*;
+    use fd_lock::AsyncRwLock;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn write_then_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let file = tokio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .await
+            .unwrap();
+        let mut l0 = AsyncRwLock::new(file);
+
+        let mut g0 = l0.write().await.unwrap();
+        g0.write_all(b"chashu cat").await.unwrap();
+        g0.flush().await.unwrap();
+        drop(g0);
+
+        let mut g0 = l0.read().await.unwrap();
+        g0.rewind().await.unwrap();
+        let mut s = String::new();
+        g0.read_to_string(&mut s).await.unwrap();
+        assert_eq!(s, "chashu cat");
+    }
+
+    #[tokio::test]
+    async fn write_waits_for_release() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(File::create(&path).unwrap());
+        let mut l1 = AsyncRwLock::new(tokio::fs::File::open(path).await.unwrap());
+
+        let g0 = l0.write().unwrap();
+        let err = l1.try_write().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        let task = tokio::spawn(async move {
+            let _g1 = l1.write().await.unwrap();
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!task.is_finished());
+        drop(g0);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled_write_releases_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(File::create(&path).unwrap());
+        let mut l1 = AsyncRwLock::new(tokio::fs::File::open(path).await.unwrap());
+
+        let g0 = l0.write().unwrap();
+        let cancelled = tokio::time::timeout(Duration::from_millis(50), l1.write()).await;
+        assert!(cancelled.is_err());
+        drop(g0);
+
+        // The abandoned acquisition completes in the background and must
+        // release the lock again.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let _g0 = loop {
+            match l0.try_write() {
+                Ok(guard) => break guard,
+                Err(err) => assert_eq!(err.kind(), ErrorKind::WouldBlock),
+            }
+            assert!(Instant::now() < deadline, "lock was leaked");
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use fd_lock::{Backend, InterruptPolicy, Liveness};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn open_rw(path: &std::path::Path) -> File {
+        File::options()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap()
+    }
+
+    #[test]
+    fn ofd_double_write_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        assert_eq!(l0.backend(), Backend::Ofd);
+
+        let g0 = l0.try_write().unwrap();
+
+        let err = l1.try_write().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        drop(g0);
+        let _g1 = l1.try_write().unwrap();
+    }
+
+    #[test]
+    fn ofd_double_read_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_read().unwrap();
+        let g1 = l0.try_read().unwrap();
+        drop(g0);
+
+        let err = l1.try_write().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        drop(g1);
+        let _g2 = l1.try_write().unwrap();
+    }
+
+    #[test]
+    fn ofd_disjoint_ranges() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let _g0 = l0.try_write_range(0..10).unwrap();
+        drop(l1.try_write_range(10..20).unwrap());
+
+        let err = l1.try_write_range(5..15).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+        let err = l1.try_read().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+    }
+
+    #[test]
+    fn ofd_overlapping_read_ranges() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_read_range(0..20).unwrap();
+        let g1 = l0.try_read_range(10..30).unwrap();
+        drop(g0);
+
+        // Only the bytes no other read guard covers are unlocked.
+        drop(l1.try_write_range(0..10).unwrap());
+        let err = l1.try_write_range(15..16).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        drop(g1);
+        let _g2 = l1.try_write_range(10..).unwrap();
+    }
+
+    #[test]
+    fn ofd_ranges_stop_before_the_reserved_byte() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let reserved = i64::MAX as u64 - 1;
+        for err in [
+            l0.try_write_range(..=reserved).unwrap_err(),
+            l0.try_write_range(reserved..reserved + 1).unwrap_err(),
+            l0.try_write_range(0..u64::MAX).unwrap_err(),
+        ] {
+            assert!(matches!(err.kind(), ErrorKind::InvalidInput));
+        }
+
+        // An unbounded end stops right before it.
+        drop(l0.try_write_range(reserved - 1..).unwrap());
+        drop(l0.try_write_range(..reserved).unwrap());
+    }
+
+    #[test]
+    fn ofd_downgrade_range() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_write_range(0..10).unwrap().downgrade().unwrap();
+        drop(l1.try_read_range(0..10).unwrap());
+
+        let err = l1.try_write_range(5..6).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        drop(g0);
+        let _g1 = l1.try_write_range(0..10).unwrap();
+    }
+
+    #[test]
+    fn ofd_single_upgradable_reader() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let l2 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_upgradable_read().unwrap();
+        let err = l1.try_upgradable_read().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+        let err = l1.try_write().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        let g2 = l2.try_read().unwrap();
+        drop(g2);
+
+        let g0 = g0.upgrade().unwrap();
+        let err = l2.try_read().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+
+        drop(g0);
+        let _g1 = l1.try_upgradable_read().unwrap();
+    }
+
+    #[test]
+    fn ofd_upgrade_waits_for_readers() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_upgradable_read().unwrap();
+        let g1 = l1.try_read().unwrap();
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                drop(g1);
+            });
+            let _g0 = g0.upgrade().unwrap();
+            let err = l1.try_read().unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+        });
+    }
+
+    #[test]
+    fn ofd_release_while_reader_waits() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let mut l2 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+
+        let g0 = l0.try_read_range(0..10).unwrap();
+        let g1 = l1.try_write_range(10..20).unwrap();
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::channel();
+            let l0 = &l0;
+            s.spawn(move || {
+                let g = l0.read_range(0..20).unwrap();
+                tx.send(()).unwrap();
+                drop(g);
+            });
+            thread::sleep(Duration::from_millis(50));
+
+            // Other readers of the lock neither wait for the blocked one nor
+            // unlock the range it is acquiring.
+            drop(l0.try_read_range(0..5).unwrap());
+            drop(g0);
+            let err = l2.try_write_range(0..10).unwrap_err();
+            assert!(matches!(err.kind(), ErrorKind::WouldBlock));
+            assert!(rx.try_recv().is_err());
+
+            drop(g1);
+            rx.recv().unwrap();
+        });
+    }
+
+    #[test]
+    fn flock_upgradable_unsupported() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(open_rw(&path));
+
+        let err = l0.try_upgradable_read().unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn flock_range_unsupported() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(open_rw(&path));
+
+        let err = l0.try_read_range(0..10).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::Unsupported));
+    }
+
+    /// A file whose descriptor can be swapped for one which can't be locked,
+    /// to make unlocking fail.
+    struct Flaky {
+        file: File,
+        path: File,
+        broken: std::cell::Cell<bool>,
+    }
+
+    impl Flaky {
+        fn new(path: &std::path::Path) -> Self {
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let file = open_rw(path);
+            let o_path = File::options()
+                .read(true)
+                .custom_flags(libc::O_PATH)
+                .open(path)
+                .unwrap();
+            Flaky {
+                file,
+                path: o_path,
+                broken: false.into(),
+            }
+        }
+    }
+
+    impl std::os::fd::AsFd for Flaky {
+        fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+            match self.broken.get() {
+                true => self.path.as_fd(),
+                false => self.file.as_fd(),
+            }
+        }
+    }
+
+    #[test]
+    fn failed_unlock_can_be_retried() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(Flaky::new(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+
+        let g0 = l0.try_write().unwrap();
+        g0.broken.set(true);
+        let err = g0.unlock().unwrap_err();
+        assert_eq!(err.error().raw_os_error(), Some(libc::EBADF));
+
+        let g0 = err.into_guard();
+        g0.broken.set(false);
+        assert!(matches!(
+            l1.try_write().unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        ));
+        g0.unlock().unwrap();
+        drop(l1.try_write().unwrap());
+    }
+
+    #[test]
+    fn failed_drop_unlock_calls_hook() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(Flaky::new(&path));
+
+        let (tx, rx) = mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        fd_lock::set_unlock_hook(Box::new(move |err| {
+            let _ = tx.lock().unwrap().send(err.raw_os_error());
+        }));
+        let g0 = l0.try_read().unwrap();
+        g0.broken.set(true);
+        drop(g0);
+        drop(fd_lock::take_unlock_hook());
+
+        assert_eq!(rx.try_recv().unwrap(), Some(libc::EBADF));
+    }
+
+    #[test]
+    fn lock_file_nofollow() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        let err = LockFile::options().nofollow(true).open(&link).unwrap_err();
+        assert!(err.to_string().contains(&*link.to_string_lossy()));
+        assert!(!path.exists());
+        LockFile::options().open(&link).unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn explicit_unlock_releases_lock() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(open_rw(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+
+        let g0 = l0.try_read().unwrap();
+        let g1 = l0.try_read().unwrap();
+        g0.unlock().unwrap();
+        assert!(matches!(
+            l1.try_write().unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        ));
+        g1.unlock().unwrap();
+        drop(l1.try_write().unwrap());
+    }
+
+    /// Installs a no-op `SIGUSR1` handler without `SA_RESTART`, so the
+    /// signal interrupts blocking syscalls, and returns the current thread.
+    fn interruptible_thread() -> libc::pthread_t {
+        extern "C" fn handler(_: libc::c_int) {}
+        // SAFETY: the handler does nothing, so it is async-signal-safe.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handler as *const () as libc::sighandler_t;
+            assert_eq!(
+                libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()),
+                0
+            );
+            libc::pthread_self()
+        }
+    }
+
+    #[test]
+    fn interrupted_write_is_retried() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::new(open_rw(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+        l1.set_interrupt_policy(InterruptPolicy::Retry);
+        let g0 = l0.write().unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let (locked_tx, locked_rx) = mpsc::channel();
+        let (relocked_tx, relocked_rx) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            tx.send(interruptible_thread()).unwrap();
+            let retried = l1.write().map(drop);
+            locked_tx.send(()).unwrap();
+            relocked_rx.recv().unwrap();
+            let returned = l1.write_with(InterruptPolicy::Return).map(drop);
+            (retried, returned)
+        });
+        let thread = rx.recv().unwrap();
+        let interrupt = || {
+            thread::sleep(Duration::from_millis(10));
+            // SAFETY: the thread is joined only after the last signal.
+            unsafe { libc::pthread_kill(thread, libc::SIGUSR1) };
+        };
+
+        // Keep signalling, as the first signals may arrive before the
+        // thread blocks.
+        for _ in 0..10 {
+            interrupt();
+        }
+        assert!(locked_rx.try_recv().is_err());
+        drop(g0);
+        locked_rx.recv().unwrap();
+
+        let g0 = l0.write().unwrap();
+        relocked_tx.send(()).unwrap();
+        while !handle.is_finished() {
+            interrupt();
+        }
+        let (retried, returned) = handle.join().unwrap();
+        retried.unwrap();
+        assert_eq!(returned.unwrap_err().kind(), ErrorKind::Interrupted);
+        drop(g0);
+    }
+
+    #[test]
+    fn ofd_write_lock_needs_write_access() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+        drop(open_rw(&path));
+
+        let mut l0 = RwLock::with_backend(File::open(&path).unwrap(), Backend::Ofd);
+        let err = fd_lock::Error::from(l0.try_write().unwrap_err());
+        assert!(matches!(err, fd_lock::Error::BadAccessMode(_)), "{err:?}");
+        drop(l0.try_read().unwrap());
+    }
+
+    #[test]
+    fn ofd_conflicting_lock_reports_range() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let mut l0 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        let l1 = RwLock::with_backend(open_rw(&path), Backend::Ofd);
+        assert_eq!(l1.holder().unwrap(), None);
+
+        let _g0 = l0.try_write_range(10..20).unwrap();
+        let holder = l1.conflicting_lock(LockMode::Shared).unwrap().unwrap();
+        assert_eq!(holder.mode(), LockMode::Exclusive);
+        assert_eq!((holder.start(), holder.end()), (10, Some(20)));
+        assert_eq!(holder.pid(), None);
+    }
+
+    #[test]
+    fn flock_holder_and_described_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(open_rw(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+        assert_eq!(l1.holder().unwrap(), None);
+
+        let _g0 = l0.try_read().unwrap();
+        assert_eq!(l1.conflicting_lock(LockMode::Shared).unwrap(), None);
+        let own = l0.holder().unwrap().unwrap();
+        assert_eq!(own.pid(), Some(std::process::id()));
+        let holder = l1.holder().unwrap().unwrap();
+        assert_eq!(holder.mode(), LockMode::Shared);
+        assert_eq!(holder.pid(), Some(std::process::id()));
+
+        let err = l1.try_write().unwrap_err();
+        assert!(!err.to_string().contains("held by"));
+        l1.set_describe_conflicts(true);
+        let err = l1.try_write().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        let pid = format!("held by process {}", std::process::id());
+        assert!(err.to_string().contains(&pid), "{err}");
+    }
+
+    #[test]
+    fn liveness_detects_exit_and_pid_reuse() {
+        let pid = std::process::id();
+        let now = SystemTime::now();
+        assert_eq!(Liveness::of_process(pid, None), Liveness::Alive);
+        assert_eq!(Liveness::of_process(pid, Some(now)), Liveness::Alive);
+        assert_eq!(Liveness::of_process(pid, Some(UNIX_EPOCH)), Liveness::Dead);
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let child_pid = child.id();
+        child.wait().unwrap();
+        assert_eq!(Liveness::of_process(child_pid, None), Liveness::Dead);
+    }
+
+    #[test]
+    fn pidfile_staleness() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pidfile");
+        assert!(!Pidfile::is_stale(&path).unwrap());
+
+        let p0 = Pidfile::options().start_time(true).create(&path).unwrap();
+        let holder = Pidfile::holder(&path).unwrap().unwrap();
+        assert_eq!(holder.liveness(), Liveness::Alive);
+        assert!(!Pidfile::is_stale(&path).unwrap());
+
+        // Unlocked, but naming a running process.
+        p0.unlock().unwrap();
+        assert!(!Pidfile::is_stale(&path).unwrap());
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        std::fs::write(&path, format!("{}\n", child.id())).unwrap();
+        child.wait().unwrap();
+        assert!(Pidfile::is_stale(&path).unwrap());
+    }
+
+    #[test]
+    fn write_with_progress_reports_holder() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(open_rw(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+
+        let mut waits = Vec::new();
+        drop(l1.write_with_progress(Duration::ZERO, |wait| waits.push(wait.clone())));
+        assert!(waits.is_empty());
+
+        let g0 = l0.try_read().unwrap();
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                drop(g0);
+            });
+            let _g1 = l1
+                .write_with_progress(Duration::from_millis(20), |wait| waits.push(wait.clone()))
+                .unwrap();
+        });
+
+        assert!(waits.len() > 1, "{waits:?}");
+        assert_eq!(waits[0].elapsed(), Duration::ZERO);
+        let holder = waits[0].holder().unwrap();
+        assert_eq!(holder.mode(), LockMode::Shared);
+        assert_eq!(holder.pid(), Some(std::process::id()));
+        let pid = format!("held by process {}", std::process::id());
+        assert!(waits[0].to_string().contains(&pid), "{}", waits[0]);
+    }
+
+    #[test]
+    fn progress_interval_is_bounded() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lockfile");
+
+        let l0 = RwLock::new(open_rw(&path));
+        let mut l1 = RwLock::new(open_rw(&path));
+
+        for (interval, max_reports) in [(Duration::ZERO, 20), (Duration::MAX, 1)] {
+            let g0 = l0.try_read().unwrap();
+            let mut reports = 0;
+            thread::scope(|s| {
+                s.spawn(move || {
+                    thread::sleep(Duration::from_millis(100));
+                    drop(g0);
+                });
+                let _g1 = l1.write_with_progress(interval, |_| reports += 1).unwrap();
+            });
+            assert!((1..=max_reports).contains(&reports), "{reports}");
+        }
+    }
+}
+
 #[cfg(windows)]
 mod windows {
     use super::*;